@@ -14,7 +14,20 @@
 uniform mat4x4 proj;
 uniform mat4x4 world;
 uniform float voxel_size = 1.0f;
-uniform samplerBuffer offsets;
+uniform vec3 origin;
+uniform vec3 dimensions;
+/* Each voxel's cell, packed as x + (y * width) + (z * width * height). */
+uniform usamplerBuffer offsets;
+uniform usamplerBuffer occlusion;
+/* Each voxel's index into the palette. */
+uniform usamplerBuffer colors;
+uniform samplerBuffer palette;
+
+/* The baked occlusion slot, (face * 4) + corner, of
+ * each vert in the cube's triangle strip. */
+const int occlusion_slots[24] = int[](16, 17, 19, 18,   3, 0, 2, 1,
+                                      23, 20, 22, 21,   4, 5, 7, 6,
+                                      12, 13, 15, 14,   9, 10, 8, 11);
 
 /* Per vertex. */
 layout (location = 0) in vec4 in_position;
@@ -26,13 +39,22 @@ out vec4 trans_color;
 
 void main()
 {
-  vec4 tex_offset = texelFetch(offsets, in_index * 2);
-  vec4 tex_color = texelFetch(offsets, (in_index * 2) + 1);
-
-  vec4 position = in_position + (tex_offset * voxel_size);
+  uint cell = texelFetch(offsets, in_index).r;
+  uvec3 dims = uvec3(dimensions);
+  vec4 tex_offset = vec4(float(cell % dims.x),
+                         float((cell / dims.x) % dims.y),
+                         float(cell / (dims.x * dims.y)),
+                         0.0f);
+  vec4 tex_color = texelFetch(palette, int(texelFetch(colors, in_index).r));
+
+  /* Offsets are grid cells; the cube is centered within its cell. */
+  vec4 position = in_position + ((tex_offset + vec4(0.5f)) * voxel_size) + vec4(origin, 0.0f);
   position.w = 1.0f;
 
-  trans_color = tex_color;
+  /* Occlusion ranges from 0 (fully occluded) to 3 (open). */
+  float ao = float(texelFetch(occlusion, (in_index * 24) + occlusion_slots[gl_VertexID]).r) / 3.0f;
+
+  trans_color = vec4(tex_color.rgb * (0.4f + (0.6f * ao)), tex_color.a);
   gl_Position = proj * world * position;
 }
 
@@ -32,7 +32,8 @@ static tls_key: local_data::Key<@mut Camera> = &local_data::Key;
 pub struct Camera
 {
   position: math::Vec3f,
-  angles: math::Vec2f,
+  /* Identity looks down +Z, with +Y up. */
+  orientation: math::Quaternion,
   
   /* Projection. */
   projection: math::Mat4x4,
@@ -67,7 +68,7 @@ impl Camera
     let c = @mut Camera
     {
       position: math::Vec3f::zero(),
-      angles: math::Vec2f::zero(),
+      orientation: math::Quaternion::identity(),
       projection: math::Mat4x4::new(),
       near_far: math::Vec2f::new(0.1, 70.0),
       fov: 100.0,
@@ -46,6 +46,7 @@ pub trait Shaderable
   fn get_uniform_location(&self, uniform: &str) -> i32;
   fn update_uniform_i32(&self, location: i32, i: i32);
   fn update_uniform_f32(&self, location: i32, i: f32);
+  fn update_uniform_vec3(&self, location: i32, vec: &Vec3f);
   fn update_uniform_mat(&self, location: i32, mat: &Mat4x4);
 }
 
@@ -151,6 +152,9 @@ impl Shader for Debug_Shader
   fn update_uniform_f32(&self, location: i32, i: f32)
   { if self.valid { shared::update_uniform_f32(location, i); } }
 
+  fn update_uniform_vec3(&self, location: i32, vec: &Vec3f)
+  { if self.valid { shared::update_uniform_vec3(location, vec); } }
+
   fn update_uniform_mat(&self, location: i32, mat: &Mat4x4)
   { if self.valid { shared::update_uniform_mat(location, mat); } }
 }
@@ -217,6 +221,9 @@ impl Shader for Release_Shader
   fn update_uniform_f32(&self, location: i32, i: f32)
   { shared::update_uniform_f32(location, i); }
 
+  fn update_uniform_vec3(&self, location: i32, vec: &Vec3f)
+  { shared::update_uniform_vec3(location, vec); }
+
   fn update_uniform_mat(&self, location: i32, mat: &Mat4x4)
   { shared::update_uniform_mat(location, mat) }
 }
@@ -356,6 +363,9 @@ pub mod shared
   pub fn update_uniform_f32(location: i32, i: f32)
   { check!(gl::Uniform1f(location, i)); }
 
+  pub fn update_uniform_vec3(location: i32, vec: &Vec3f)
+  { check!(gl::Uniform3f(location, vec.x, vec.y, vec.z)); }
+
   pub fn update_uniform_mat(location: i32, mat: &Mat4x4)
   { 
     check_unsafe!(gl::UniformMatrix4fv(
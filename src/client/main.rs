@@ -225,6 +225,8 @@ impl console::Functor for Client
             director.unshift(game_renderer_state as @mut state::State);
           }
           gfx::Camera::get_active().reset(); /* Jump back to the origin. */
+          if game_state.spawns.len() > 0
+          { gfx::Camera::get_active().position = game_state.spawns[0]; }
         }
 
         if err.len() > 0
@@ -9,10 +9,11 @@
       TODO
 */
 
-use super::{ Joint_Info, Bound, Base_Frame, Frame_Data, Frame_Skeleton, Skeleton_Joint };
+use super::{ Joint_Info, Base_Frame, Frame_Data, Frame_Skeleton, Skeleton_Joint };
 use std::char;
 use std::rt::io::buffered::BufferedReader;
 use std::rt::io::{ Reader, File };
+use math;
 use log::Log;
 
 #[macro_escape]
@@ -22,7 +23,7 @@ mod macros;
 struct Animation
 {
   joint_infos: ~[Joint_Info],
-  bounds: ~[Bound],
+  bounds: ~[math::BB3],
 
   base_frames: ~[Base_Frame],
   frames: ~[Frame_Data],
@@ -221,7 +222,7 @@ impl Animation
           ignore_line!();
           for _ in range(0, self.num_frames)
           {
-            let mut bound = Bound::new();
+            let mut bound = math::BB3::zero();
             read_junk!(); /* ( */
             read_type!(bound.min.x);
             read_type!(bound.min.y);
@@ -37,7 +37,6 @@ pub use self::vertex::Vertex;
 
 /* Animation */
 pub use self::joint_info::Joint_Info;
-pub use self::bound::Bound;
 pub use self::frame::{ Base_Frame, Frame_Data };
 pub use self::skeleton::{ Skeleton_Joint, Frame_Skeleton };
 pub use self::animation::Animation;
@@ -63,8 +62,6 @@ pub mod triangle;
 /* Animation */
 #[path = "animation/joint_info.rs"]
 pub mod joint_info;
-#[path = "animation/bound.rs"]
-pub mod bound;
 #[path = "animation/frame.rs"]
 pub mod frame;
 #[path = "animation/skeleton.rs"]
@@ -70,12 +70,7 @@ impl State for gfx::Camera
 
 
     /* Update where the camera is looking. */
-    let lookat = math::Vec3f::new
-    (
-      self.angles.x.sin() * self.angles.y.cos(),
-      self.angles.y.sin(),
-      self.angles.x.cos() * self.angles.y.cos()
-    );
+    let lookat = self.orientation.rotate_vec(&math::Vec3f::new(0.0, 0.0, 1.0));
     self.view = math::Mat4x4::new_lookat(self.position, 
                                     self.position + lookat, /* TODO: * focus for zoom */
                                     math::Vec3f::new(0.0, 1.0, 0.0));
@@ -141,20 +136,20 @@ impl State for gfx::Camera
     let dx = x - (self.window_size.x / 2) as f32;
     let dy = y - (self.window_size.y / 2) as f32;
 
-    self.angles.x -= dx as f32 * self.look_speed;
-    self.angles.y -= dy as f32 * self.look_speed;
-    
-    /* Wrap X. */
-    if self.angles.x < -f32::consts::PI
-    { self.angles.x += f32::consts::PI * 2.0; }
-    else if self.angles.x > f32::consts::PI
-    { self.angles.x -= f32::consts::PI * 2.0; }
-
-    /* Clamp Y. */
-    if self.angles.y < -f32::consts::PI * 0.49
-    { self.angles.y = -f32::consts::PI * 0.49; }
-    else if self.angles.y > f32::consts::PI * 0.49
-    { self.angles.y = f32::consts::PI * 0.49; }
+    /* Yaw about the world's up, then pitch about the camera's own side. */
+    let up = math::Vec3f::new(0.0, 1.0, 0.0);
+    let side = math::Vec3f::new(1.0, 0.0, 0.0);
+    let yaw = math::Quaternion::new_from_axis(&up, -dx * self.look_speed);
+    let pitch = math::Quaternion::new_from_axis(&side, dy * self.look_speed);
+    self.orientation = yaw * self.orientation;
+    self.orientation.normalize();
+
+    /* Only pitch if it doesn't take us past looking straight up or down. */
+    let mut pitched = self.orientation * pitch;
+    pitched.normalize();
+    let lookat = pitched.rotate_vec(&math::Vec3f::new(0.0, 0.0, 1.0));
+    if lookat.y.abs() < (f32::consts::PI * 0.49).sin()
+    { self.orientation = pitched; }
 
     self.window.set_cursor_pos( (self.window_size.x / 2) as f64, 
                                 (self.window_size.y / 2) as f64);
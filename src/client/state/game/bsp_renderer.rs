@@ -37,6 +37,8 @@ pub struct BSP_Renderer
 
   vao: gl2::GLuint,
   vbo: gl2::GLuint, 
+  /* Zero when the game's map isn't a BSP. */
+  vert_count: i32,
 
   shader: @mut gfx::Shader,
   proj_loc: gl2::GLint,
@@ -53,6 +55,7 @@ impl BSP_Renderer
 
       vao: 0,
       vbo: 0,
+      vert_count: 0,
 
       shader: gfx::Shader_Builder::new_with_files("data/shaders/color.vert", "data/shaders/color.frag"),
       proj_loc: 0,
@@ -76,7 +79,17 @@ impl BSP_Renderer
 
     check!(gl2::bind_vertex_array(self.vao));
     check!(gl2::bind_buffer(gl2::ARRAY_BUFFER, self.vbo));
-    check!(gl2::buffer_data(gl2::ARRAY_BUFFER, self.game_renderer.game.bsp_map.verts, gl2::STATIC_DRAW));
+
+    /* OBJ, PLY, and generated maps have no BSP to draw. */
+    match self.game_renderer.game.bsp_map
+    {
+      Some(ref map) =>
+      {
+        check!(gl2::buffer_data(gl2::ARRAY_BUFFER, map.verts, gl2::STATIC_DRAW));
+        self.vert_count = map.verts.len() as i32;
+      }
+      None => { log_error!("Game map is not a BSP; nothing to render"); }
+    }
 
     /* Setup vertex attribs. */
     check!(gl2::bind_buffer(gl2::ARRAY_BUFFER, self.vbo));
@@ -95,7 +108,7 @@ impl BSP_Renderer
   fn render_mesh(&self)
   {
     check!(gl2::bind_vertex_array(self.vao));
-    check!(gl2::draw_arrays(gl2::TRIANGLES, 0, self.game_renderer.game.bsp_map.verts.len() as i32));
+    check!(gl2::draw_arrays(gl2::TRIANGLES, 0, self.vert_count));
     check!(gl2::bind_vertex_array(0));
   }
 }
@@ -15,8 +15,10 @@
 */
 
 use extra;
+use math;
 use obj;
-use super::State;
+use console;
+use super::{ State, Director, Deferred };
 use log::Log;
 
 #[macro_escape]
@@ -25,21 +27,46 @@ mod macros;
 
 pub struct Game
 {
-  bsp_map: obj::BSP_Map,
+  /* Only available when the map came from a BSP. */
+  bsp_map: Option<obj::BSP_Map>,
   voxel_map: @mut obj::Voxel_Map,
+  /* Only available when the map was generated. */
+  spawns: ~[math::Vec3f],
 }
 
 impl Game
 {
+  /* Map names with a mesh extension (.obj, .ply) are loaded
+   * as arbitrary meshes and gen:<seed> generates an arena;
+   * anything else is a BSP. */
   pub fn new(map_name: &str) -> Result<@mut Game, ~str>
   {
-    let bmap = obj::BSP_Map::new(~"data/maps/" + map_name + ".bsp");
-    if bmap.is_err()
-    { return Err(bmap.unwrap_err()); }
-    let bmap = bmap.unwrap();
+    if map_name.starts_with("gen:")
+    { return Game::new_generated(map_name.slice_from(4)); }
+
+    let mut bmap = None;
+    let mut mesh = None;
+    if obj::Mesh::is_supported(map_name)
+    {
+      let m = obj::Mesh::new(~"data/maps/" + map_name);
+      if m.is_err()
+      { return Err(m.unwrap_err()); }
+      mesh = Some(m.unwrap());
+    }
+    else
+    {
+      let b = obj::BSP_Map::new(~"data/maps/" + map_name + ".bsp");
+      if b.is_err()
+      { return Err(b.unwrap_err()); }
+      bmap = Some(b.unwrap());
+    }
 
     let start_time = extra::time::precise_time_s();
-    let vmap = obj::Voxel_Map::new(bmap.tris, 300);
+    let vmap = match bmap
+    {
+      Some(ref b) => obj::Voxel_Map::new(b.tris, 300, obj::voxel::Conservative_Mode),
+      None => obj::Voxel_Map::new(mesh.get_ref().tris, 300, obj::voxel::Conservative_Mode),
+    };
     let time = extra::time::precise_time_s() - start_time;
     log_info!("Voxelization took {} seconds", time);
     if vmap.is_err()
@@ -50,10 +77,54 @@ impl Game
     {
       voxel_map: vmap,
       bsp_map: bmap,
+      spawns: ~[],
     };
+    Game::add_functions(game);
 
     Ok(game)
   }
+
+  fn new_generated(seed: &str) -> Result<@mut Game, ~str>
+  {
+    let seed = match from_str::<u64>(seed.trim())
+    {
+      Some(seed) => seed,
+      None => { return Err(format!("Invalid arena seed: {}", seed)); }
+    };
+
+    let start_time = extra::time::precise_time_s();
+    let arena = obj::Voxel_Map::new_arena(seed, &obj::voxel::Arena_Params::new());
+    let time = extra::time::precise_time_s() - start_time;
+    log_info!("Generation took {} seconds", time);
+    if arena.is_err()
+    { return Err(arena.unwrap_err()); }
+    let arena = arena.unwrap();
+
+    let game = @mut Game
+    {
+      voxel_map: arena.map,
+      bsp_map: None,
+      spawns: arena.spawns,
+    };
+    Game::add_functions(game);
+
+    Ok(game)
+  }
+
+  fn add_functions(game: @mut Game)
+  {
+    struct Tmp_Deferred
+    { game: @mut Game }
+    impl Deferred for Tmp_Deferred
+    {
+      fn call(&mut self)
+      {
+        console::Console::get().add_function(~"export_map", self.game as @mut console::Functor);
+        console::Console::get().add_function(~"export_bsp", self.game as @mut console::Functor);
+      }
+    }
+    Director::push_deferred(@mut Tmp_Deferred{ game: game } as @mut Deferred);
+  }
 }
 
 impl State for Game
@@ -72,3 +143,38 @@ impl State for Game
   { false }
 }
 
+/* Debugging aids for looking at maps outside of the game.
+ * Ex: export_map arena.obj
+ * Ex: export_bsp q3ctf1.ply */
+impl console::Functor for Game
+{
+  fn call(&mut self, name: &str, params: &str) -> (bool, ~str)
+  {
+    let file = params.trim();
+    if file.len() == 0
+    { return (false, format!("\\2Error: \\1Usage: {} <file>", name)); }
+
+    let res = match name
+    {
+      /* The voxel map's visible surface, as seen in game. */
+      "export_map" => { self.voxel_map.export_mesh(file) }
+      /* The triangles the voxel map was built from. */
+      "export_bsp" =>
+      {
+        match self.bsp_map
+        {
+          Some(ref map) => { map.export_mesh(file) }
+          None => { Err(~"The current map is not a BSP") }
+        }
+      }
+      _ => { Err(format!("Invalid function '{}'", name)) }
+    };
+
+    match res
+    {
+      Ok(()) => { (true, format!("Exported to \\5{}\\1", file)) }
+      Err(err) => { (false, format!("\\2Error: \\1{}", err)) }
+    }
+  }
+}
+
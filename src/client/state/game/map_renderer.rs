@@ -38,10 +38,23 @@ pub struct Map_Renderer
   vox_vbo: gl2::GLuint,
   offset_tex_vbo: gl2::GLuint,
   offset_tex: gl2::GLuint,
+  occlusion_tex_vbo: gl2::GLuint,
+  occlusion_tex: gl2::GLuint,
+  color_tex_vbo: gl2::GLuint,
+  color_tex: gl2::GLuint,
+  palette_tex_vbo: gl2::GLuint,
+  palette_tex: gl2::GLuint,
+  /* The map's revision when its voxels were last uploaded. */
+  revision: u32,
   ibos: ~[gl2::GLuint],
   curr_ibo: u32,
   visible_voxels: Option<~[u32]>,
   prev_visible_voxel_count: u32,
+  /* The renderer's own copy of the map's states, for culling. This is
+   * what gets moved out to the worker, so that the map's states stay
+   * put for collision, ray casts, and carving. It's copied once, then
+   * kept up to date with only the map's dirty states. */
+  states: Option<~[u32]>,
 
   /* states, visible */
   map_stream: extra::comm::DuplexStream<(cell::Cell<~[u32]>, cell::Cell<~[u32]>), (~[u32], ~[u32])>,
@@ -52,7 +65,12 @@ pub struct Map_Renderer
   proj_loc: gl2::GLint,
   world_loc: gl2::GLint,
   voxel_size_loc: gl2::GLint,
+  origin_loc: gl2::GLint,
+  dimensions_loc: gl2::GLint,
   offsets_loc: gl2::GLint,
+  occlusion_loc: gl2::GLint,
+  colors_loc: gl2::GLint,
+  palette_loc: gl2::GLint,
 }
 
 impl Map_Renderer
@@ -69,10 +87,18 @@ impl Map_Renderer
       vox_vbo: 0,
       offset_tex_vbo: 0,
       offset_tex: 0,
+      occlusion_tex_vbo: 0,
+      occlusion_tex: 0,
+      color_tex_vbo: 0,
+      color_tex: 0,
+      palette_tex_vbo: 0,
+      palette_tex: 0,
+      revision: 0,
       ibos: vec::from_elem(2, 2u32),
       curr_ibo: 0,
-      visible_voxels: Some(vec::from_elem((map.resolution * map.resolution * map.resolution) as uint, 0u32)),
+      visible_voxels: Some(vec::from_elem((map.dimensions.x * map.dimensions.y * map.dimensions.z) as uint, 0u32)),
       prev_visible_voxel_count: 0,
+      states: map.states.clone(),
 
       map_stream: local_stream,
 
@@ -82,7 +108,12 @@ impl Map_Renderer
       proj_loc: 0,
       world_loc: 0,
       voxel_size_loc: 0,
+      origin_loc: 0,
+      dimensions_loc: 0,
       offsets_loc: 0,
+      occlusion_loc: 0,
+      colors_loc: 0,
+      palette_loc: 0,
     };
 
     /* Single voxel that will be instance-rendered. */
@@ -112,26 +143,26 @@ impl Map_Renderer
     log_assert!(names.len() == 1);
     mr.vao = names[0];
 
-    let names = check!(gl2::gen_buffers(4));
-    log_assert!(names.len() == 4);
+    let names = check!(gl2::gen_buffers(7));
+    log_assert!(names.len() == 7);
     mr.vox_vbo = names[0];
     mr.offset_tex_vbo = names[1];
     mr.ibos[0] = names[2];
     mr.ibos[1] = names[3];
+    mr.occlusion_tex_vbo = names[4];
+    mr.color_tex_vbo = names[5];
+    mr.palette_tex_vbo = names[6];
 
     check!(gl2::bind_buffer(gl2::ARRAY_BUFFER, mr.vox_vbo));
     check!(gl2::buffer_data(gl2::ARRAY_BUFFER, voxel, gl2::STATIC_DRAW));
 
     check!(gl2::bind_buffer(gl2::ARRAY_BUFFER, mr.ibos[0]));
-    let ibo_buf = vec::from_elem((mr.map.resolution * mr.map.resolution * mr.map.resolution) as uint, 0);
+    let ibo_buf = vec::from_elem((mr.map.dimensions.x * mr.map.dimensions.y * mr.map.dimensions.z) as uint, 0);
     check!(gl2::buffer_data(gl2::ARRAY_BUFFER, ibo_buf, gl2::DYNAMIC_DRAW));
 
     check!(gl2::bind_buffer(gl2::ARRAY_BUFFER, mr.ibos[1]));
     check!(gl2::buffer_data(gl2::ARRAY_BUFFER, ibo_buf, gl2::DYNAMIC_DRAW));
 
-    check!(gl2::bind_buffer(gl2::TEXTURE_BUFFER, mr.offset_tex_vbo));
-    check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, mr.map.voxels, gl2::STATIC_DRAW));
-
     /* Setup vertex attribs. */
     check!(gl2::bind_vertex_array(mr.vao));
 
@@ -149,12 +180,16 @@ impl Map_Renderer
     check!(gl2::enable_vertex_attrib_array(1));
     check!(gl2::vertex_attrib_divisor(1, 1));
 
-    /* Generate buffer texture. */
-    let name = check!(gl2::gen_textures(1));
-    log_assert!(name.len() == 1);
-    mr.offset_tex = name[0];
-    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, mr.offset_tex));
-    check!(gl2::tex_buffer(gl2::TEXTURE_BUFFER, 0x8815 /* RGB32F */, mr.offset_tex_vbo));
+    /* Generate buffer textures. */
+    let names = check!(gl2::gen_textures(4));
+    log_assert!(names.len() == 4);
+    mr.offset_tex = names[0];
+    mr.occlusion_tex = names[1];
+    mr.color_tex = names[2];
+    mr.palette_tex = names[3];
+
+    mr.map.take_dirty(); /* Already in the copy. */
+    mr.upload_voxels();
 
     /* Console functions. */
     struct Tmp_Deferred
@@ -172,6 +207,66 @@ impl Map_Renderer
     mr
   }
 
+  /* Uploads everything which is indexed by voxel. */
+  fn upload_voxels(&mut self)
+  {
+    /* Voxels are uploaded in their compact form: a packed cell
+     * and a palette index each, rather than two full vec3s. */
+    let compact = self.map.to_compact(voxel::Large_Palette);
+    check!(gl2::bind_buffer(gl2::TEXTURE_BUFFER, self.offset_tex_vbo));
+    check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, compact.cells, gl2::STATIC_DRAW));
+
+    check!(gl2::bind_buffer(gl2::TEXTURE_BUFFER, self.color_tex_vbo));
+    let color_format = match compact.indices
+    {
+      voxel::compact::Small_Indices(ref indices) =>
+      { check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, *indices, gl2::STATIC_DRAW)); 0x8232 /* R8UI */ }
+      voxel::compact::Large_Indices(ref indices) =>
+      { check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, *indices, gl2::STATIC_DRAW)); 0x8234 /* R16UI */ }
+    };
+
+    check!(gl2::bind_buffer(gl2::TEXTURE_BUFFER, self.palette_tex_vbo));
+    check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, compact.palette, gl2::STATIC_DRAW));
+
+    check!(gl2::bind_buffer(gl2::TEXTURE_BUFFER, self.occlusion_tex_vbo));
+    check!(gl2::buffer_data(gl2::TEXTURE_BUFFER, self.map.occlusion, gl2::STATIC_DRAW));
+
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.offset_tex));
+    check!(gl2::tex_buffer(gl2::TEXTURE_BUFFER, 0x8236 /* R32UI */, self.offset_tex_vbo));
+
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.occlusion_tex));
+    check!(gl2::tex_buffer(gl2::TEXTURE_BUFFER, 0x8232 /* R8UI */, self.occlusion_tex_vbo));
+
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.color_tex));
+    check!(gl2::tex_buffer(gl2::TEXTURE_BUFFER, color_format, self.color_tex_vbo));
+
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.palette_tex));
+    check!(gl2::tex_buffer(gl2::TEXTURE_BUFFER, 0x8058 /* RGBA8 */, self.palette_tex_vbo));
+
+    self.revision = self.map.revision;
+  }
+
+  /* Copies whatever states the map changed into our copy, which
+   * must be home; not out with the worker. */
+  fn sync_states(&mut self)
+  {
+    let (start, end) = match self.map.take_dirty()
+    {
+      Some(range) => range,
+      None => { return; }
+    };
+
+    match (&self.map.states, &mut self.states)
+    {
+      (&Some(ref src), &Some(ref mut dst)) =>
+      {
+        for i in range(start, end)
+        { dst[i] = src[i]; }
+      }
+      _ => { log_error!("Voxel states are unavailable; unable to sync them"); }
+    }
+  }
+
   #[fixed_stack_segment]
   pub fn update_visibility(&mut self)
   {
@@ -179,22 +274,19 @@ impl Map_Renderer
 
     let cam = gfx::Camera::get_active();
     let dist = (cam.near_far.y  / self.map.voxel_size) as i32; /* How far the camera can see. */
-    let res = self.map.resolution as f32;
-    let pos = math::Vec3f::new(cam.position.x / self.map.voxel_size,
-                               cam.position.y / self.map.voxel_size,
-                               cam.position.z / self.map.voxel_size)
-                               + math::Vec3f::new(res / 2.0, res / 2.0, res / 2.0);
+    let dims = self.map.dimensions;
+    let pos = self.map.world_to_grid(&cam.position);
     let start = math::Vec3i::new
     (
-      (pos.x - dist as f32).clamp(&0.0, &(res - 1.0)) as i32,
-      (pos.y - dist as f32).clamp(&0.0, &(res - 1.0)) as i32,
-      (pos.z - dist as f32).clamp(&0.0, &(res - 1.0)) as i32
+      (pos.x - dist as f32).clamp(&0.0, &((dims.x - 1) as f32)) as i32,
+      (pos.y - dist as f32).clamp(&0.0, &((dims.y - 1) as f32)) as i32,
+      (pos.z - dist as f32).clamp(&0.0, &((dims.z - 1) as f32)) as i32
     );
     let end = math::Vec3i::new
     (
-      (pos.x + dist as f32).clamp(&0.0, &(res - 1.0)) as i32,
-      (pos.y + dist as f32).clamp(&0.0, &(res - 1.0)) as i32,
-      (pos.z + dist as f32).clamp(&0.0, &(res - 1.0)) as i32
+      (pos.x + dist as f32).clamp(&0.0, &((dims.x - 1) as f32)) as i32,
+      (pos.y + dist as f32).clamp(&0.0, &((dims.y - 1) as f32)) as i32,
+      (pos.z + dist as f32).clamp(&0.0, &((dims.z - 1) as f32)) as i32
     );
 
     self.visible_voxels.get_mut_ref().clear();
@@ -202,17 +294,16 @@ impl Map_Renderer
     /* Updating visible voxels is an expensive task. To remedy this,
      * the work is done on a background thread that has a shared OpenGL
      * context. While that work is being done, the map renderer will
-     * not have visible voxels or voxel states, since they're moved
-     * to the task. Once the task is finished, however, the fields are
-     * sent back. */
+     * not have visible voxels or its copy of the voxel states, since
+     * they're moved to the task. Once the task is finished, however,
+     * the fields are sent back. */
     let (local_stream, remote_stream) = extra::comm::DuplexStream();
     self.map_stream = local_stream;
 
     /* Send out the voxel states and visible voxels. */
-    self.map_stream.send((cell::Cell::new(self.map.states.take_unwrap()), cell::Cell::new(self.visible_voxels.take_unwrap())));
+    self.map_stream.send((cell::Cell::new(self.states.take_unwrap()), cell::Cell::new(self.visible_voxels.take_unwrap())));
 
     /* Start the new background task of culling far-away voxels. */
-    let resolution = self.map.resolution;
     let ibo = self.ibos[self.curr_ibo];
     do gfx::Worker::new_task
     {
@@ -226,7 +317,8 @@ impl Map_Renderer
         {
           for x in range(start.x, end.x)
           {
-            let index = (z * ((resolution * resolution) as i32)) + (y * (resolution as i32)) + x;
+            /* The map can't be sent to the worker; only its dimensions. */
+            let index = voxel::map::grid_index(&dims, &math::Vec3i::new(x, y, z));
             if (states[index] & voxel::Visible) != 0
             { visible_voxels.push(states[index] & !voxel::Visible); }
           }
@@ -262,9 +354,17 @@ impl State for Map_Renderer
     self.proj_loc = self.shader.get_uniform_location("proj");
     self.world_loc = self.shader.get_uniform_location("world");
     self.voxel_size_loc = self.shader.get_uniform_location("voxel_size");
+    self.origin_loc = self.shader.get_uniform_location("origin");
     self.offsets_loc = self.shader.get_uniform_location("offsets");
+    self.occlusion_loc = self.shader.get_uniform_location("occlusion");
+    self.dimensions_loc = self.shader.get_uniform_location("dimensions");
+    self.colors_loc = self.shader.get_uniform_location("colors");
+    self.palette_loc = self.shader.get_uniform_location("palette");
 
     self.shader.update_uniform_i32(self.offsets_loc, 0);
+    self.shader.update_uniform_i32(self.occlusion_loc, 1);
+    self.shader.update_uniform_i32(self.colors_loc, 2);
+    self.shader.update_uniform_i32(self.palette_loc, 3);
 
     self.update_visibility();
   }
@@ -277,13 +377,17 @@ impl State for Map_Renderer
      * need to wait for it to finish so that it doesn't try
      * to update us when we're dead. */
     let (states, visible_voxels) = self.map_stream.recv();
-    self.map.states = Some(states);
+    self.states = Some(states);
     self.visible_voxels = Some(visible_voxels);
 
     /* Cleanup GL. */
     check!(gl2::delete_vertex_arrays(&[self.vao]));
     check!(gl2::delete_buffers(&[self.vox_vbo, self.offset_tex_vbo,
-                                 self.ibos[0], self.ibos[1]]));
+                                 self.ibos[0], self.ibos[1],
+                                 self.occlusion_tex_vbo, self.color_tex_vbo,
+                                 self.palette_tex_vbo]));
+    check!(gl2::delete_textures(&[self.offset_tex, self.occlusion_tex,
+                                  self.color_tex, self.palette_tex]));
   }
 
   fn get_key(&self) -> &str
@@ -300,10 +404,13 @@ impl State for Map_Renderer
 
     /* Extract the new data. */
     let (states, visible_voxels) = self.map_stream.recv();
-    self.map.states = Some(states);
+    self.states = Some(states);
     self.visible_voxels = Some(visible_voxels);
-    
-    /* TODO: Work goes here. */
+
+    /* The map was carved or patched since the last upload. */
+    self.sync_states();
+    if self.revision != self.map.revision
+    { self.upload_voxels(); }
 
     /* Swap the current IBO and begin updating the old one. */
     if self.curr_ibo == 0
@@ -323,6 +430,10 @@ impl State for Map_Renderer
     self.shader.update_uniform_mat(self.proj_loc, &camera.projection);
     self.shader.update_uniform_mat(self.world_loc, &camera.view);
     self.shader.update_uniform_f32(self.voxel_size_loc, self.map.voxel_size);
+    self.shader.update_uniform_vec3(self.origin_loc, &self.map.origin);
+    let dims = self.map.dimensions;
+    self.shader.update_uniform_vec3(self.dimensions_loc,
+                                    &math::Vec3f::new(dims.x as f32, dims.y as f32, dims.z as f32));
 
     check!(gl2::bind_vertex_array(self.vao));
 
@@ -334,6 +445,13 @@ impl State for Map_Renderer
     check!(gl2::enable_vertex_attrib_array(1));
     check!(gl2::vertex_attrib_divisor(1, 1));
 
+    check!(gl2::active_texture(gl2::TEXTURE3));
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.palette_tex));
+    check!(gl2::active_texture(gl2::TEXTURE2));
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.color_tex));
+    check!(gl2::active_texture(gl2::TEXTURE1));
+    check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.occlusion_tex));
+    check!(gl2::active_texture(gl2::TEXTURE0));
     check!(gl2::bind_texture(gl2::TEXTURE_BUFFER, self.offset_tex));
 
     if self.wireframe
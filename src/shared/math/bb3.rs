@@ -6,37 +6,108 @@
     File: shared/math/bb3.rs
     Author: Jesse 'Jeaye' Wilkerson
     Description:
-      A 3D bounding box.
+      A 3D axis-aligned bounding box.
 */
 
-pub struct BB3 /* TODO: Check super on this shit. */
+use std::cmp;
+
+pub struct BB3
 {
-  top_left: super::Vec3f,
-  bottom_right: super::Vec3f,
+  min: super::Vec3f,
+  max: super::Vec3f,
 }
 
 impl BB3
 {
-  pub fn new(t_left: super::Vec3f, b_right: super::Vec3f) -> BB3
+  pub fn new(min: super::Vec3f, max: super::Vec3f) -> BB3
+  { BB3{ min: min, max: max } }
+
+  pub fn zero() -> BB3
+  { BB3{ min: super::Vec3f::zero(), max: super::Vec3f::zero() } }
+
+  /* The smallest box around every point; zero if there are none. */
+  pub fn from_points(points: &[super::Vec3f]) -> BB3
+  {
+    if points.len() == 0
+    { return BB3::zero(); }
+
+    let mut bb = BB3::new(points[0], points[0]);
+    for point in points.iter()
+    { bb.expand(point); }
+    bb
+  }
+
+  /* Grows the box, if need be, to hold the point. */
+  pub fn expand(&mut self, point: &super::Vec3f)
   {
-    BB3{ top_left: t_left, bottom_right: b_right }
+    self.min = super::Vec3f::new(cmp::min(self.min.x, point.x),
+                                 cmp::min(self.min.y, point.y),
+                                 cmp::min(self.min.z, point.z));
+    self.max = super::Vec3f::new(cmp::max(self.max.x, point.x),
+                                 cmp::max(self.max.y, point.y),
+                                 cmp::max(self.max.z, point.z));
   }
 
-  pub fn zero() -> BB3
+  pub fn union(&self, other: &BB3) -> BB3
+  {
+    let mut bb = *self;
+    bb.expand(&other.min);
+    bb.expand(&other.max);
+    bb
+  }
+
+  /* None if the boxes don't overlap. Boxes which only touch
+   * give a flat (or degenerate) box. */
+  pub fn intersection(&self, other: &BB3) -> Option<BB3>
+  {
+    let min = super::Vec3f::new(cmp::max(self.min.x, other.min.x),
+                                cmp::max(self.min.y, other.min.y),
+                                cmp::max(self.min.z, other.min.z));
+    let max = super::Vec3f::new(cmp::min(self.max.x, other.max.x),
+                                cmp::min(self.max.y, other.max.y),
+                                cmp::min(self.max.z, other.max.z));
+    if min.x > max.x || min.y > max.y || min.z > max.z
+    { None }
+    else
+    { Some(BB3::new(min, max)) }
+  }
+
+  /* Inclusive of the box's faces. */
+  pub fn contains(&self, point: &super::Vec3f) -> bool
   {
-    BB3{  top_left: super::Vec3f::zero(), bottom_right: super::Vec3f::zero() } 
+    point.x >= self.min.x && point.x <= self.max.x &&
+    point.y >= self.min.y && point.y <= self.max.y &&
+    point.z >= self.min.z && point.z <= self.max.z
   }
 
   pub fn center(&self) -> super::Vec3f
-  { super::Vec3f::new( (self.bottom_right.x - self.top_left.x) / 2.0,
-                      (self.top_left.y - self.bottom_right.y) / 2.0,
-                      (self.top_left.z - self.bottom_right.z) / 2.0 )
+  { (self.min + self.max) * 0.5 }
+
+  /* Half of the box's size along each axis. */
+  pub fn extents(&self) -> super::Vec3f
+  { (self.max - self.min) * 0.5 }
+
+  /* Indexed by bits: 1 picks max.x, 2 max.y, and 4 max.z. */
+  pub fn corners(&self) -> [super::Vec3f, ..8]
+  {
+    let mut corners = [super::Vec3f::zero(), ..8];
+    for i in range(0u, 8u)
+    {
+      corners[i] = super::Vec3f::new(if i & 1 == 0 { self.min.x } else { self.max.x },
+                                     if i & 2 == 0 { self.min.y } else { self.max.y },
+                                     if i & 4 == 0 { self.min.z } else { self.max.z });
+    }
+    corners
   }
 
-  pub fn center_with_offset(&self, offset: super::Vec3f) -> super::Vec3f
-  { super::Vec3f::new( ((self.bottom_right.x - self.top_left.x) / 2.0) + offset.x,
-                      ((self.top_left.y - self.bottom_right.y) / 2.0) + offset.y,
-                      ((self.top_left.z - self.bottom_right.z) / 2.0) + offset.z )
+  /* The box around this one's transformed corners. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> BB3
+  {
+    let corners = self.corners();
+    let first = mat.transform_point(&corners[0]);
+    let mut bb = BB3::new(first, first);
+    for corner in corners.iter()
+    { bb.expand(&mat.transform_point(corner)); }
+    bb
   }
 }
-
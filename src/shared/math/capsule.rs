@@ -0,0 +1,68 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/capsule.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A capsule, as the segment from start to end
+      swollen by a radius; the usual shape for
+      players.
+*/
+
+use super::intersect;
+use super::intersect::Hit;
+
+pub struct Capsule
+{
+  start: super::Vec3f,
+  end: super::Vec3f,
+  radius: f32,
+}
+
+impl Capsule
+{
+  pub fn new(start: super::Vec3f, end: super::Vec3f, radius: f32) -> Capsule
+  { Capsule { start: start, end: end, radius: radius } }
+
+  /* The closest point on the capsule's inner segment. */
+  pub fn closest_point(&self, point: &super::Vec3f) -> super::Vec3f
+  {
+    let dir = self.end - self.start;
+    let len_sq = dir.dot(&dir);
+    if len_sq.approx_eq(&0.0)
+    { return self.start; }
+
+    let t = ((*point - self.start).dot(&dir) / len_sq).clamp(&0.0, &1.0);
+    self.start + (dir * t)
+  }
+
+  /* Inclusive of the surface. */
+  pub fn contains(&self, point: &super::Vec3f) -> bool
+  {
+    let offset = *point - self.closest_point(point);
+    offset.dot(&offset) <= self.radius * self.radius
+  }
+
+  /* The normal pushes the capsule out of the box. */
+  pub fn intersects_aabb(&self, bb: &super::BB3) -> Option<Hit>
+  { intersect::capsule_aabb(&self.start, &self.end, self.radius, &bb.min, &bb.max) }
+
+  pub fn bounds(&self) -> super::BB3
+  {
+    let r = super::Vec3f::new(self.radius, self.radius, self.radius);
+    super::BB3::new(self.start.min(&self.end) - r, self.start.max(&self.end) + r)
+  }
+
+  /* Like Sphere::transform, the radius grows by the largest scale. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> Capsule
+  {
+    Capsule
+    {
+      start: mat.transform_point(&self.start),
+      end: mat.transform_point(&self.end),
+      radius: self.radius * mat.max_scale(),
+    }
+  }
+}
@@ -0,0 +1,312 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/curve.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Bezier curves, Catmull-Rom splines, and
+      bi-quadratic patches, along with arc-length
+      tables for moving along a curve at an even
+      speed.
+
+      Curves are evaluated for t in [0, 1], and
+      derivatives are with respect to that t.
+*/
+
+use std::{ cmp, vec };
+use super::Vec3f;
+
+pub fn quadratic(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, t: f32) -> Vec3f
+{
+  let inv = 1.0 - t;
+  (*p0 * (inv * inv)) + (*p1 * (2.0 * inv * t)) + (*p2 * (t * t))
+}
+
+pub fn quadratic_derivative(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, t: f32) -> Vec3f
+{ ((*p1 - *p0) * (2.0 * (1.0 - t))) + ((*p2 - *p1) * (2.0 * t)) }
+
+pub fn cubic(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, p3: &Vec3f, t: f32) -> Vec3f
+{
+  let inv = 1.0 - t;
+  (*p0 * (inv * inv * inv)) +
+  (*p1 * (3.0 * inv * inv * t)) +
+  (*p2 * (3.0 * inv * t * t)) +
+  (*p3 * (t * t * t))
+}
+
+pub fn cubic_derivative(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, p3: &Vec3f, t: f32) -> Vec3f
+{
+  let inv = 1.0 - t;
+  ((*p1 - *p0) * (3.0 * inv * inv)) +
+  ((*p2 - *p1) * (6.0 * inv * t)) +
+  ((*p3 - *p2) * (3.0 * t * t))
+}
+
+/* The uniform Catmull-Rom segment from p1 (at t = 0) to p2 (at t = 1);
+ * p0 and p3 only shape the tangents. */
+pub fn catmull_rom(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, p3: &Vec3f, t: f32) -> Vec3f
+{
+  let t2 = t * t;
+  let t3 = t2 * t;
+  ((*p1 * 2.0) +
+   ((*p2 - *p0) * t) +
+   ((*p0 * 2.0 - *p1 * 5.0 + *p2 * 4.0 - *p3) * t2) +
+   ((*p1 * 3.0 - *p0 - *p2 * 3.0 + *p3) * t3)) * 0.5
+}
+
+pub fn catmull_rom_derivative(p0: &Vec3f, p1: &Vec3f, p2: &Vec3f, p3: &Vec3f, t: f32) -> Vec3f
+{
+  ((*p2 - *p0) +
+   ((*p0 * 2.0 - *p1 * 5.0 + *p2 * 4.0 - *p3) * (2.0 * t)) +
+   ((*p1 * 3.0 - *p0 - *p2 * 3.0 + *p3) * (3.0 * t * t))) * 0.5
+}
+
+/* A bi-quadratic patch, as BSP patches are built from. The control
+ * grid is indexed [v][u], each row being a quadratic curve in u. */
+pub fn biquadratic(control: &[[Vec3f, ..3], ..3], u: f32, v: f32) -> Vec3f
+{
+  let row = |r: uint| quadratic(&control[r][0], &control[r][1], &control[r][2], u);
+  quadratic(&row(0), &row(1), &row(2), v)
+}
+
+/* The partial derivatives, along u and then v. Their cross product
+ * is the surface normal. */
+pub fn biquadratic_derivatives(control: &[[Vec3f, ..3], ..3], u: f32, v: f32) -> (Vec3f, Vec3f)
+{
+  let row = |r: uint| quadratic(&control[r][0], &control[r][1], &control[r][2], u);
+  let row_du = |r: uint| quadratic_derivative(&control[r][0], &control[r][1], &control[r][2], u);
+
+  (quadratic(&row_du(0), &row_du(1), &row_du(2), v),
+   quadratic_derivative(&row(0), &row(1), &row(2), v))
+}
+
+/* A Catmull-Rom spline through every point. The end points are
+ * repeated to give the first and last segments their tangents. */
+pub struct Spline
+{
+  points: ~[Vec3f],
+}
+
+impl Spline
+{
+  pub fn new(points: ~[Vec3f]) -> Spline
+  { Spline { points: points } }
+
+  pub fn segment_count(&self) -> uint
+  {
+    if self.points.len() < 2
+    { 0 }
+    else
+    { self.points.len() - 1 }
+  }
+
+  /* The segment t falls in, and how far along it. */
+  fn locate(&self, t: f32) -> (uint, f32)
+  {
+    let segments = self.segment_count();
+    let s = t.clamp(&0.0, &1.0) * (segments as f32);
+    let i = cmp::min(s.floor() as uint, segments - 1);
+    (i, s - (i as f32))
+  }
+
+  /* The four control points around segment i. */
+  fn segment(&self, i: uint) -> (Vec3f, Vec3f, Vec3f, Vec3f)
+  {
+    let last = self.points.len() - 1;
+    (self.points[if i == 0 { 0 } else { i - 1 }],
+     self.points[i],
+     self.points[i + 1],
+     self.points[cmp::min(i + 2, last)])
+  }
+
+  /* The whole spline spans t in [0, 1], each segment taking an
+   * equal share regardless of its length. */
+  pub fn evaluate(&self, t: f32) -> Vec3f
+  {
+    match self.points.len()
+    {
+      0 => Vec3f::zero(),
+      1 => self.points[0],
+      _ =>
+      {
+        let (i, local) = self.locate(t);
+        let (p0, p1, p2, p3) = self.segment(i);
+        catmull_rom(&p0, &p1, &p2, &p3, local)
+      }
+    }
+  }
+
+  pub fn derivative(&self, t: f32) -> Vec3f
+  {
+    if self.points.len() < 2
+    { return Vec3f::zero(); }
+
+    let (i, local) = self.locate(t);
+    let (p0, p1, p2, p3) = self.segment(i);
+    catmull_rom_derivative(&p0, &p1, &p2, &p3, local) * (self.segment_count() as f32)
+  }
+
+  /* Samples the spline at the given number of even steps in t. */
+  pub fn arc_lengths(&self, steps: uint) -> Arc_Lengths
+  {
+    let steps = cmp::max(steps, 1);
+    let samples = vec::from_fn(steps + 1, |i| self.evaluate((i as f32) / (steps as f32)));
+    Arc_Lengths::new(samples)
+  }
+}
+
+/* Distance travelled along a curve, from points sampled at even
+ * steps in t, for mapping distance back to t. More samples give a
+ * closer fit to the curve. */
+pub struct Arc_Lengths
+{
+  /* lengths[i] is the distance to sample i, so lengths[0] is zero. */
+  lengths: ~[f32],
+}
+
+impl Arc_Lengths
+{
+  pub fn new(samples: &[Vec3f]) -> Arc_Lengths
+  {
+    let mut lengths = vec::with_capacity(samples.len());
+    let mut total = 0.0f32;
+    for i in range(0, samples.len())
+    {
+      if i > 0
+      { total += (samples[i] - samples[i - 1]).length(); }
+      lengths.push(total);
+    }
+
+    Arc_Lengths { lengths: lengths }
+  }
+
+  pub fn total(&self) -> f32
+  {
+    if self.lengths.len() == 0
+    { 0.0 }
+    else
+    { self.lengths[self.lengths.len() - 1] }
+  }
+
+  /* The t at which the curve has covered the distance, clamped
+   * to the curve's ends. */
+  pub fn param_at(&self, distance: f32) -> f32
+  {
+    let count = self.lengths.len();
+    if count < 2 || !(self.total() > 0.0) || distance <= 0.0
+    { return 0.0; }
+    if distance >= self.total()
+    { return 1.0; }
+
+    /* The first sample at least as far as the distance. */
+    let mut low = 0;
+    let mut high = count - 1;
+    while low < high
+    {
+      let mid = (low + high) / 2;
+      if self.lengths[mid] < distance
+      { low = mid + 1; }
+      else
+      { high = mid; }
+    }
+
+    let start = self.lengths[low - 1];
+    let span = self.lengths[low] - start;
+    let frac = if span > 0.0 { (distance - start) / span } else { 0.0 };
+    (((low - 1) as f32) + frac) / ((count - 1) as f32)
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use super::super::Vec3f;
+
+  fn near(a: f32, b: f32, tolerance: f32) -> bool
+  { (a - b).abs() < tolerance }
+
+  fn close(a: &Vec3f, b: &Vec3f) -> bool
+  { near(a.x, b.x, 0.0001) && near(a.y, b.y, 0.0001) && near(a.z, b.z, 0.0001) }
+
+  fn controls() -> (Vec3f, Vec3f, Vec3f, Vec3f)
+  { (Vec3f::new(0.0, 0.0, 0.0), Vec3f::new(1.0, 2.0, 0.0), Vec3f::new(3.0, 2.0, 1.0), Vec3f::new(4.0, 0.0, -1.0)) }
+
+  #[test]
+  fn bezier()
+  {
+    let (p0, p1, p2, p3) = controls();
+
+    assert!(close(&quadratic(&p0, &p1, &p2, 0.0), &p0));
+    assert!(close(&quadratic(&p0, &p1, &p2, 1.0), &p2));
+    assert!(close(&quadratic_derivative(&p0, &p1, &p2, 0.0), &((p1 - p0) * 2.0)));
+    assert!(close(&quadratic_derivative(&p0, &p1, &p2, 1.0), &((p2 - p1) * 2.0)));
+
+    assert!(close(&cubic(&p0, &p1, &p2, &p3, 0.0), &p0));
+    assert!(close(&cubic(&p0, &p1, &p2, &p3, 1.0), &p3));
+    assert!(close(&cubic(&p0, &p1, &p2, &p3, 0.5), &((p0 + (p1 * 3.0) + (p2 * 3.0) + p3) * 0.125)));
+    assert!(close(&cubic_derivative(&p0, &p1, &p2, &p3, 0.0), &((p1 - p0) * 3.0)));
+    assert!(close(&cubic_derivative(&p0, &p1, &p2, &p3, 1.0), &((p3 - p2) * 3.0)));
+  }
+
+  #[test]
+  fn catmull_rom_segment()
+  {
+    let (p0, p1, p2, p3) = controls();
+
+    /* Through the middle two points, with tangents from their
+     * neighbours. */
+    assert!(close(&catmull_rom(&p0, &p1, &p2, &p3, 0.0), &p1));
+    assert!(close(&catmull_rom(&p0, &p1, &p2, &p3, 1.0), &p2));
+    assert!(close(&catmull_rom_derivative(&p0, &p1, &p2, &p3, 0.0), &((p2 - p0) * 0.5)));
+    assert!(close(&catmull_rom_derivative(&p0, &p1, &p2, &p3, 1.0), &((p3 - p1) * 0.5)));
+
+    /* Evenly spaced along a line, it's just the line. */
+    let step = Vec3f::new(1.0, 1.0, 0.0);
+    let line = |i: f32| step * i;
+    assert!(close(&catmull_rom(&line(0.0), &line(1.0), &line(2.0), &line(3.0), 0.25), &line(1.25)));
+  }
+
+  #[test]
+  fn spline()
+  {
+    let points = ~[ Vec3f::new(0.0, 0.0, 0.0), Vec3f::new(1.0, 0.0, 0.0),
+                    Vec3f::new(2.0, 0.0, 0.0), Vec3f::new(3.0, 0.0, 0.0) ];
+    let spline = Spline::new(points.clone());
+    assert!(spline.segment_count() == 3);
+
+    assert!(close(&spline.evaluate(0.0), &points[0]));
+    assert!(close(&spline.evaluate(1.0), &points[3]));
+    assert!(close(&spline.evaluate(0.5), &Vec3f::new(1.5, 0.0, 0.0)));
+
+    /* The middle segment covers a unit in a third of t. */
+    assert!(close(&spline.derivative(0.5), &Vec3f::new(3.0, 0.0, 0.0)));
+  }
+
+  #[test]
+  fn arc_lengths()
+  {
+    /* A straight line, though its ends ease in and out, so even
+     * steps in t aren't even steps in distance. */
+    let spline = Spline::new(~[ Vec3f::new(0.0, 0.0, 0.0), Vec3f::new(1.0, 0.0, 0.0),
+                                Vec3f::new(2.0, 0.0, 0.0), Vec3f::new(3.0, 0.0, 0.0) ]);
+    let lengths = spline.arc_lengths(300);
+    assert!(near(lengths.total(), 3.0, 0.0001));
+
+    for &distance in [ 0.1, 0.5, 1.5, 2.2, 2.9 ].iter()
+    {
+      let t = lengths.param_at(distance);
+      assert!(near(spline.evaluate(t).x, distance, 0.001));
+    }
+
+    assert!(lengths.param_at(-1.0) == 0.0);
+    assert!(lengths.param_at(0.0) == 0.0);
+    assert!(lengths.param_at(4.0) == 1.0);
+
+    let empty = Arc_Lengths::new(&[]);
+    assert!(empty.total() == 0.0);
+    assert!(empty.param_at(1.0) == 0.0);
+  }
+}
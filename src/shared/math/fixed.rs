@@ -0,0 +1,434 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/fixed.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      16.16 fixed-point scalars, vectors, and
+      quaternions. Every operation is integer
+      math, so the client and server get the same
+      bits regardless of compiler or optimization
+      level, which f32 doesn't promise.
+
+      The range is about +/-32767 with a step of
+      1/65536. Results past the range saturate at
+      its ends rather than wrapping. Dot products
+      and lengths are summed in i64 and narrowed
+      only at the end, so large terms which cancel
+      out still give the right answer. Converting
+      from floats is
+      only as deterministic as the float itself,
+      so convert once, up front.
+*/
+
+use std::{ cmp, i32 };
+
+static FRACTION_BITS: uint = 16;
+static ONE: i32 = 1 << FRACTION_BITS;
+
+#[deriving(Clone, Eq, Ord)]
+pub struct Fixed
+{
+  raw: i32,
+}
+
+impl Fixed
+{
+  /* From the raw 16.16 bits, as saved or sent over the wire. */
+  pub fn new(raw: i32) -> Fixed
+  { Fixed { raw: raw } }
+
+  pub fn new_from_i32(num: i32) -> Fixed
+  { Fixed { raw: saturate((num as i64) << FRACTION_BITS) } }
+
+  /* Rounded to the nearest step. */
+  pub fn new_from_f32(num: f32) -> Fixed
+  {
+    let raw = ((num as f64) * (ONE as f64)).round();
+    Fixed { raw: raw.clamp(&(i32::min_value as f64), &(i32::max_value as f64)) as i32 }
+  }
+
+  pub fn zero() -> Fixed
+  { Fixed { raw: 0 } }
+  pub fn one() -> Fixed
+  { Fixed { raw: ONE } }
+
+  /* Rounded toward negative infinity. */
+  pub fn to_i32(&self) -> i32
+  { self.raw >> FRACTION_BITS }
+
+  pub fn to_f32(&self) -> f32
+  { (self.raw as f32) / (ONE as f32) }
+
+  pub fn abs(&self) -> Fixed
+  { if self.raw < 0 { -*self } else { *self } }
+
+  pub fn min(&self, rhs: &Fixed) -> Fixed
+  { if self.raw < rhs.raw { *self } else { *rhs } }
+  pub fn max(&self, rhs: &Fixed) -> Fixed
+  { if self.raw > rhs.raw { *self } else { *rhs } }
+  pub fn clamp(&self, min: &Fixed, max: &Fixed) -> Fixed
+  { self.max(min).min(max) }
+
+  pub fn floor(&self) -> Fixed
+  { Fixed { raw: self.raw & !(ONE - 1) } }
+
+  /* Rounded down to the nearest step; zero for negative numbers. */
+  pub fn sqrt(&self) -> Fixed
+  {
+    if self.raw <= 0
+    { return Fixed::zero(); }
+    Fixed { raw: isqrt((self.raw as u64) << FRACTION_BITS) as i32 }
+  }
+
+  pub fn to_str(&self) -> ~str
+  { format!("{}", self.to_f32()) }
+}
+
+impl Add<Fixed, Fixed> for Fixed
+{
+  fn add(&self, rhs: &Fixed) -> Fixed
+  { Fixed { raw: saturate((self.raw as i64) + (rhs.raw as i64)) } }
+}
+
+impl Sub<Fixed, Fixed> for Fixed
+{
+  fn sub(&self, rhs: &Fixed) -> Fixed
+  { Fixed { raw: saturate((self.raw as i64) - (rhs.raw as i64)) } }
+}
+
+impl Mul<Fixed, Fixed> for Fixed
+{
+  fn mul(&self, rhs: &Fixed) -> Fixed
+  { Fixed { raw: saturate(((self.raw as i64) * (rhs.raw as i64)) >> FRACTION_BITS) } }
+}
+
+/* Fails on division by zero, as integer division does. */
+impl Div<Fixed, Fixed> for Fixed
+{
+  fn div(&self, rhs: &Fixed) -> Fixed
+  { Fixed { raw: saturate(((self.raw as i64) << FRACTION_BITS) / (rhs.raw as i64)) } }
+}
+
+impl Neg<Fixed> for Fixed
+{
+  fn neg(&self) -> Fixed
+  { Fixed { raw: saturate(-(self.raw as i64)) } }
+}
+
+#[deriving(Clone, Eq)]
+pub struct Fixed_Vec3
+{
+  x: Fixed,
+  y: Fixed,
+  z: Fixed,
+}
+
+impl Fixed_Vec3
+{
+  pub fn new(x: Fixed, y: Fixed, z: Fixed) -> Fixed_Vec3
+  { Fixed_Vec3 { x: x, y: y, z: z } }
+
+  pub fn new_from_vec(vec: &super::Vec3f) -> Fixed_Vec3
+  {
+    Fixed_Vec3::new(Fixed::new_from_f32(vec.x),
+                    Fixed::new_from_f32(vec.y),
+                    Fixed::new_from_f32(vec.z))
+  }
+
+  pub fn zero() -> Fixed_Vec3
+  { Fixed_Vec3::new(Fixed::zero(), Fixed::zero(), Fixed::zero()) }
+
+  pub fn to_vec(&self) -> super::Vec3f
+  { super::Vec3f::new(self.x.to_f32(), self.y.to_f32(), self.z.to_f32()) }
+
+  pub fn dot(&self, rhs: &Fixed_Vec3) -> Fixed
+  { dot([ self.x, self.y, self.z ], [ rhs.x, rhs.y, rhs.z ]) }
+
+  pub fn cross(&self, rhs: &Fixed_Vec3) -> Fixed_Vec3
+  {
+    Fixed_Vec3::new((self.y * rhs.z) - (self.z * rhs.y),
+                    (self.z * rhs.x) - (self.x * rhs.z),
+                    (self.x * rhs.y) - (self.y * rhs.x))
+  }
+
+  pub fn length(&self) -> Fixed
+  { length([ self.x, self.y, self.z ]) }
+
+  /* Zero vectors are left as they are. */
+  pub fn normalize(&mut self)
+  {
+    let len = self.length();
+    if len == Fixed::zero()
+    { return; }
+
+    self.x = self.x / len;
+    self.y = self.y / len;
+    self.z = self.z / len;
+  }
+
+  pub fn lerp(&self, rhs: &Fixed_Vec3, interp: Fixed) -> Fixed_Vec3
+  { *self + ((*rhs - *self) * interp) }
+
+  pub fn to_str(&self) -> ~str
+  { format!("({}, {}, {})", self.x.to_str(), self.y.to_str(), self.z.to_str()) }
+}
+
+impl Add<Fixed_Vec3, Fixed_Vec3> for Fixed_Vec3
+{
+  fn add(&self, rhs: &Fixed_Vec3) -> Fixed_Vec3
+  { Fixed_Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z) }
+}
+
+impl Sub<Fixed_Vec3, Fixed_Vec3> for Fixed_Vec3
+{
+  fn sub(&self, rhs: &Fixed_Vec3) -> Fixed_Vec3
+  { Fixed_Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z) }
+}
+
+impl Mul<Fixed, Fixed_Vec3> for Fixed_Vec3
+{
+  fn mul(&self, rhs: &Fixed) -> Fixed_Vec3
+  { Fixed_Vec3::new(self.x * *rhs, self.y * *rhs, self.z * *rhs) }
+}
+
+impl Neg<Fixed_Vec3> for Fixed_Vec3
+{
+  fn neg(&self) -> Fixed_Vec3
+  { Fixed_Vec3::new(-self.x, -self.y, -self.z) }
+}
+
+#[deriving(Clone, Eq)]
+pub struct Fixed_Quaternion
+{
+  x: Fixed,
+  y: Fixed,
+  z: Fixed,
+  w: Fixed,
+}
+
+impl Fixed_Quaternion
+{
+  pub fn new(x: Fixed, y: Fixed, z: Fixed, w: Fixed) -> Fixed_Quaternion
+  { Fixed_Quaternion { x: x, y: y, z: z, w: w } }
+
+  /* There's no fixed-point trig, so rotations are built with
+   * Quaternion and converted. */
+  pub fn new_from_quaternion(quat: &super::Quaternion) -> Fixed_Quaternion
+  {
+    Fixed_Quaternion::new(Fixed::new_from_f32(quat.x),
+                          Fixed::new_from_f32(quat.y),
+                          Fixed::new_from_f32(quat.z),
+                          Fixed::new_from_f32(quat.w))
+  }
+
+  pub fn identity() -> Fixed_Quaternion
+  { Fixed_Quaternion::new(Fixed::zero(), Fixed::zero(), Fixed::zero(), Fixed::one()) }
+
+  pub fn to_quaternion(&self) -> super::Quaternion
+  { super::Quaternion::new(self.x.to_f32(), self.y.to_f32(), self.z.to_f32(), self.w.to_f32()) }
+
+  pub fn get_conjugate(&self) -> Fixed_Quaternion
+  { Fixed_Quaternion::new(-self.x, -self.y, -self.z, self.w) }
+
+  pub fn dot(&self, rhs: &Fixed_Quaternion) -> Fixed
+  { dot([ self.x, self.y, self.z, self.w ], [ rhs.x, rhs.y, rhs.z, rhs.w ]) }
+
+  /* Rounding creeps in with every multiply, so renormalize
+   * orientations that are built up over many steps. */
+  pub fn normalize(&mut self)
+  {
+    let len = length([ self.x, self.y, self.z, self.w ]);
+    if len == Fixed::zero()
+    { return; }
+
+    self.x = self.x / len;
+    self.y = self.y / len;
+    self.z = self.z / len;
+    self.w = self.w / len;
+  }
+
+  /* Assumes a unit quaternion: v + 2w(u x v) + 2(u x (u x v)). */
+  pub fn rotate_vec(&self, vec: &Fixed_Vec3) -> Fixed_Vec3
+  {
+    let two = Fixed::new_from_i32(2);
+    let u = Fixed_Vec3::new(self.x, self.y, self.z);
+    let t = u.cross(vec) * two;
+    *vec + (t * self.w) + u.cross(&t)
+  }
+
+  pub fn to_str(&self) -> ~str
+  {
+    format!("({}, {}, {}, {})",
+          self.x.to_str(),
+          self.y.to_str(),
+          self.z.to_str(),
+          self.w.to_str())
+  }
+}
+
+impl Mul<Fixed_Quaternion, Fixed_Quaternion> for Fixed_Quaternion
+{
+  fn mul(&self, rhs: &Fixed_Quaternion) -> Fixed_Quaternion
+  {
+    Fixed_Quaternion::new
+    (
+      (self.x * rhs.w) + (self.w * rhs.x) + (self.y * rhs.z) - (self.z * rhs.y),
+      (self.y * rhs.w) + (self.w * rhs.y) + (self.z * rhs.x) - (self.x * rhs.z),
+      (self.z * rhs.w) + (self.w * rhs.z) + (self.x * rhs.y) - (self.y * rhs.x),
+      (self.w * rhs.w) - (self.x * rhs.x) - (self.y * rhs.y) - (self.z * rhs.z)
+    )
+  }
+}
+
+/* Narrows raw bits to the range, pinning them at its ends. */
+fn saturate(raw: i64) -> i32
+{ raw.clamp(&(i32::min_value as i64), &(i32::max_value as i64)) as i32 }
+
+/* Each product is shifted back to 16.16 on its own, as Mul does,
+ * but the sum is kept in i64 until the end. */
+fn dot(lhs: &[Fixed], rhs: &[Fixed]) -> Fixed
+{
+  let mut sum = 0i64;
+  for i in range(0, lhs.len())
+  { sum += ((lhs[i].raw as i64) * (rhs[i].raw as i64)) >> FRACTION_BITS; }
+  Fixed { raw: saturate(sum) }
+}
+
+/* The square root of the sum of squares, which are summed as
+ * 32.32 so that no precision is lost before the root. Up to four
+ * components fit in a u64, once each is kept off of i32's
+ * minimum. */
+fn length(components: &[Fixed]) -> Fixed
+{
+  let mut sum = 0u64;
+  for c in components.iter()
+  {
+    let mag = cmp::min((c.raw as i64).abs(), i32::max_value as i64) as u64;
+    sum += mag * mag;
+  }
+  Fixed { raw: saturate(isqrt(sum) as i64) }
+}
+
+/* Integer square root, by the digit-by-digit method. */
+fn isqrt(num: u64) -> u64
+{
+  let mut rem = num;
+  let mut res = 0u64;
+  let mut bit = 1u64 << 62;
+  while bit > rem
+  { bit >>= 2; }
+
+  while bit != 0
+  {
+    if rem >= res + bit
+    {
+      rem -= res + bit;
+      res = (res >> 1) + bit;
+    }
+    else
+    { res >>= 1; }
+    bit >>= 2;
+  }
+  res
+}
+
+#[cfg(test)]
+mod test
+{
+  use std::i32;
+  use super::{ Fixed, Fixed_Vec3, Fixed_Quaternion };
+
+  fn num(n: f32) -> Fixed
+  { Fixed::new_from_f32(n) }
+
+  fn vec(x: f32, y: f32, z: f32) -> Fixed_Vec3
+  { Fixed_Vec3::new(num(x), num(y), num(z)) }
+
+  /* Within a few steps, for results which round along the way. */
+  fn near(a: Fixed, b: Fixed) -> bool
+  { (a - b).abs().raw <= 2 }
+
+  #[test]
+  fn known_answers()
+  {
+    assert!(num(1.5) * num(2.0) == num(3.0));
+    assert!(num(-2.5) * num(4.0) == num(-10.0));
+    assert!(num(0.5) * num(0.5) == num(0.25));
+
+    assert!(num(1.0) / num(4.0) == num(0.25));
+    assert!(num(7.0) / num(-2.0) == num(-3.5));
+
+    assert!(num(4.0).sqrt() == num(2.0));
+    assert!(num(2.0).sqrt() == Fixed::new(92681)); /* sqrt(2) * 65536, rounded down. */
+    assert!(num(0.0).sqrt() == Fixed::zero());
+    assert!(num(-4.0).sqrt() == Fixed::zero());
+
+    assert!(num(-3.75).to_i32() == -4);
+    assert!(num(-3.75).floor() == num(-4.0));
+    assert!(Fixed::new_from_i32(-12) == num(-12.0));
+  }
+
+  #[test]
+  fn saturates()
+  {
+    let max = Fixed::new(i32::max_value);
+    let min = Fixed::new(i32::min_value);
+
+    assert!(num(200.0) * num(200.0) == max);
+    assert!(num(-200.0) * num(200.0) == min);
+    assert!(num(30000.0) / num(0.5) == max);
+    assert!(max + num(1.0) == max);
+    assert!(min - num(1.0) == min);
+    assert!(-min == max);
+    assert!(min.abs() == max);
+    assert!(num(1.0e10) == max);
+    assert!(Fixed::new_from_i32(40000) == max);
+
+    /* Still fine just inside the range. */
+    assert!(num(181.0) * num(181.0) == num(32761.0));
+    assert!(max.sqrt() == Fixed::new(11863283)); /* sqrt(2^47 - 2^16), rounded down. */
+  }
+
+  #[test]
+  #[should_fail]
+  fn divide_by_zero()
+  { num(1.0) / Fixed::zero(); }
+
+  #[test]
+  fn dot_and_length()
+  {
+    assert!(vec(100.0, 100.0, 100.0).dot(&vec(1.0, 1.0, 1.0)) == num(300.0));
+
+    /* Each term is past the range, but they cancel out. */
+    assert!(vec(20000.0, -20000.0, 0.0).dot(&vec(2.0, 2.0, 0.0)) == Fixed::zero());
+    assert!(vec(200.0, 0.0, 0.0).dot(&vec(200.0, 0.0, 0.0)) == Fixed::new(i32::max_value));
+
+    assert!(vec(3.0, 4.0, 0.0).length() == num(5.0));
+    assert!(near(vec(200.0, 200.0, 0.0).length(), num(282.842712)));
+  }
+
+  #[test]
+  fn normalize()
+  {
+    let mut v = vec(3.0, 4.0, 0.0);
+    v.normalize();
+    assert!(near(v.x, num(0.6)) && near(v.y, num(0.8)) && v.z == Fixed::zero());
+
+    /* Too long for dot(self).sqrt(), which would saturate. */
+    let mut v = vec(200.0, -200.0, 0.0);
+    v.normalize();
+    assert!(near(v.x, num(0.707107)) && near(v.y, num(-0.707107)));
+    assert!(near(v.length(), Fixed::one()));
+
+    let mut v = Fixed_Vec3::zero();
+    v.normalize();
+    assert!(v == Fixed_Vec3::zero());
+
+    let mut q = Fixed_Quaternion::new(Fixed::zero(), Fixed::zero(), Fixed::zero(), num(2.0));
+    q.normalize();
+    assert!(q == Fixed_Quaternion::identity());
+  }
+}
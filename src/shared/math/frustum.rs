@@ -0,0 +1,203 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/frustum.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A view frustum, as six inward-facing planes
+      pulled from a combined projection and view
+      matrix, for culling.
+*/
+
+pub enum Containment
+{
+  Outside_Frustum,
+  Inside_Frustum,
+  Intersects_Frustum,
+}
+
+pub struct Frustum
+{
+  /* Left, right, bottom, top, near, far. Each is a normalized
+   * (x, y, z) normal, pointing inward, and w as the distance, so
+   * a point p is inside when dot(normal, p) + w >= 0. */
+  planes: [super::Vec4f, ..6],
+}
+
+impl Frustum
+{
+  /* The matrix is projection * view, in the math sense. Since
+   * Mat4x4's multiply applies its left-hand side first, that's
+   * view * projection here. Planes end up in world space. */
+  pub fn new(mat: &super::Mat4x4) -> Frustum
+  {
+    let d = &mat.data;
+    let row = |r: uint| super::Vec4f::new(d[0][r], d[1][r], d[2][r], d[3][r]);
+    let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+    let mut frustum = Frustum
+    {
+      planes: [ w + x, w - x,
+                w + y, w - y,
+                w + z, w - z ],
+    };
+    for plane in frustum.planes.mut_iter()
+    {
+      let len = super::Vec3f::new(plane.x, plane.y, plane.z).length();
+      if !len.approx_eq(&0.0)
+      { *plane = *plane * (1.0 / len); }
+    }
+
+    frustum
+  }
+
+  /* Signed distance from the plane; positive is inside. */
+  fn distance(plane: &super::Vec4f, point: &super::Vec3f) -> f32
+  { (plane.x * point.x) + (plane.y * point.y) + (plane.z * point.z) + plane.w }
+
+  pub fn contains_point(&self, point: &super::Vec3f) -> bool
+  { self.planes.iter().all(|plane| Frustum::distance(plane, point) >= 0.0) }
+
+  pub fn intersects_sphere(&self, center: &super::Vec3f, radius: f32) -> bool
+  {
+    match self.classify_sphere(center, radius)
+    {
+      Outside_Frustum => false,
+      _ => true,
+    }
+  }
+
+  pub fn intersects_aabb(&self, min: &super::Vec3f, max: &super::Vec3f) -> bool
+  {
+    match self.classify_aabb(min, max)
+    {
+      Outside_Frustum => false,
+      _ => true,
+    }
+  }
+
+  pub fn classify_sphere(&self, center: &super::Vec3f, radius: f32) -> Containment
+  {
+    let mut result = Inside_Frustum;
+    for plane in self.planes.iter()
+    {
+      let dist = Frustum::distance(plane, center);
+      if dist < -radius
+      { return Outside_Frustum; }
+      else if dist < radius
+      { result = Intersects_Frustum; }
+    }
+    result
+  }
+
+  /* Conservative, like any plane-at-a-time test: a box outside
+   * near a corner of the frustum can come back as intersecting. */
+  pub fn classify_aabb(&self, min: &super::Vec3f, max: &super::Vec3f) -> Containment
+  {
+    let mut result = Inside_Frustum;
+    for plane in self.planes.iter()
+    {
+      /* The corners furthest along, and furthest against, the normal. */
+      let positive = super::Vec3f::new(if plane.x >= 0.0 { max.x } else { min.x },
+                                        if plane.y >= 0.0 { max.y } else { min.y },
+                                        if plane.z >= 0.0 { max.z } else { min.z });
+      let negative = super::Vec3f::new(if plane.x >= 0.0 { min.x } else { max.x },
+                                        if plane.y >= 0.0 { min.y } else { max.y },
+                                        if plane.z >= 0.0 { min.z } else { max.z });
+
+      if Frustum::distance(plane, &positive) < 0.0
+      { return Outside_Frustum; }
+      else if Frustum::distance(plane, &negative) < 0.0
+      { result = Intersects_Frustum; }
+    }
+    result
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::{ Frustum, Containment, Outside_Frustum, Inside_Frustum, Intersects_Frustum };
+  use super::super::{ Vec3f, Mat4x4 };
+
+  /* A 90 degree square projection, from 1 to 100, looking down -Z
+   * from the origin. At any depth, the sides are as far out as the
+   * depth is deep. */
+  fn frustum() -> Frustum
+  { Frustum::new(&Mat4x4::new_perspective(90.0, 1.0, 1.0, 100.0)) }
+
+  fn name(result: Containment) -> &'static str
+  {
+    match result
+    {
+      Outside_Frustum => "outside",
+      Inside_Frustum => "inside",
+      Intersects_Frustum => "intersects",
+    }
+  }
+
+  fn sphere(frustum: &Frustum, x: f32, y: f32, z: f32, radius: f32) -> &'static str
+  { name(frustum.classify_sphere(&Vec3f::new(x, y, z), radius)) }
+
+  fn aabb(frustum: &Frustum, min: (f32, f32, f32), max: (f32, f32, f32)) -> &'static str
+  {
+    let (x0, y0, z0) = min;
+    let (x1, y1, z1) = max;
+    name(frustum.classify_aabb(&Vec3f::new(x0, y0, z0), &Vec3f::new(x1, y1, z1)))
+  }
+
+  #[test]
+  fn classify_sphere()
+  {
+    let f = frustum();
+    assert_eq!(sphere(&f, 0.0, 0.0, -10.0, 1.0), "inside");
+    assert_eq!(sphere(&f, 0.0, 0.0, 10.0, 1.0), "outside");
+    assert_eq!(sphere(&f, 20.0, 0.0, -10.0, 1.0), "outside");
+    assert_eq!(sphere(&f, 0.0, 0.0, -200.0, 1.0), "outside");
+
+    /* Across the near, right, top and far planes. */
+    assert_eq!(sphere(&f, 0.0, 0.0, -1.0, 0.5), "intersects");
+    assert_eq!(sphere(&f, 10.0, 0.0, -10.0, 1.0), "intersects");
+    assert_eq!(sphere(&f, 0.0, 10.0, -10.0, 1.0), "intersects");
+    assert_eq!(sphere(&f, 0.0, 0.0, -100.0, 1.0), "intersects");
+
+    assert!(f.intersects_sphere(&Vec3f::new(10.0, 0.0, -10.0), 1.0));
+    assert!(!f.intersects_sphere(&Vec3f::new(0.0, 0.0, 10.0), 1.0));
+  }
+
+  #[test]
+  fn classify_aabb()
+  {
+    let f = frustum();
+    assert_eq!(aabb(&f, (-1.0, -1.0, -11.0), (1.0, 1.0, -9.0)), "inside");
+    assert_eq!(aabb(&f, (-1.0, -1.0, 5.0), (1.0, 1.0, 7.0)), "outside");
+    assert_eq!(aabb(&f, (15.0, -1.0, -11.0), (17.0, 1.0, -9.0)), "outside");
+    assert_eq!(aabb(&f, (-1.0, -1.0, -300.0), (1.0, 1.0, -200.0)), "outside");
+
+    /* Across the right plane, and across the near plane. */
+    assert_eq!(aabb(&f, (8.0, -1.0, -11.0), (12.0, 1.0, -9.0)), "intersects");
+    assert_eq!(aabb(&f, (-0.5, -0.5, -2.0), (0.5, 0.5, 0.5)), "intersects");
+
+    /* Big enough to hold the whole frustum. */
+    assert_eq!(aabb(&f, (-500.0, -500.0, -500.0), (500.0, 500.0, 500.0)), "intersects");
+
+    assert!(f.intersects_aabb(&Vec3f::new(8.0, -1.0, -11.0), &Vec3f::new(12.0, 1.0, -9.0)));
+    assert!(!f.intersects_aabb(&Vec3f::new(-1.0, -1.0, 5.0), &Vec3f::new(1.0, 1.0, 7.0)));
+  }
+
+  #[test]
+  fn moved_camera()
+  {
+    /* The camera at z = 50; the view applies first. */
+    let view = Mat4x4::new_translation(0.0, 0.0, -50.0);
+    let f = Frustum::new(&(view * Mat4x4::new_perspective(90.0, 1.0, 1.0, 100.0)));
+
+    assert_eq!(sphere(&f, 0.0, 0.0, 40.0, 1.0), "inside");
+    assert_eq!(sphere(&f, 0.0, 0.0, 60.0, 1.0), "outside");
+    assert_eq!(aabb(&f, (-1.0, -1.0, -1.0), (1.0, 1.0, 1.0)), "inside");
+    assert!(f.contains_point(&Vec3f::new(0.0, 0.0, 0.0)));
+    assert!(!f.contains_point(&Vec3f::new(0.0, 0.0, 55.0)));
+  }
+}
@@ -0,0 +1,405 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/intersect.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Intersection tests between rays, segments,
+      planes, boxes, spheres, capsules, and
+      triangles.
+
+      Rays are origin + (dir * t) for t >= 0; dir
+      needn't be normalized, and distances are in
+      multiples of it. Planes are the points p for
+      which dot(normal, p) + dist = 0. Boxes are
+      axis-aligned, given by their min and max.
+*/
+
+use std::{ cmp, f32 };
+use super::Vec3f;
+
+pub struct Hit
+{
+  /* For rays and segments, the t of the hit. For overlaps, how
+   * far the first shape reaches into the second. */
+  distance: f32,
+  /* For rays and segments, the surface normal, facing the ray. For
+   * overlaps, the direction to move the first shape out of the second. */
+  normal: Vec3f,
+}
+
+pub fn ray_plane(origin: &Vec3f, dir: &Vec3f, normal: &Vec3f, dist: f32) -> Option<Hit>
+{
+  let denom = normal.dot(dir);
+  if denom.approx_eq(&0.0)
+  { return None; } /* Parallel. */
+
+  let t = -(normal.dot(origin) + dist) / denom;
+  if t < 0.0
+  { return None; }
+
+  Some(Hit { distance: t, normal: if denom < 0.0 { *normal } else { -*normal } })
+}
+
+/* The slab test. Starting inside the box is a hit at distance zero,
+ * with a zero normal. */
+pub fn ray_aabb(origin: &Vec3f, dir: &Vec3f, min: &Vec3f, max: &Vec3f) -> Option<Hit>
+{
+  let mut enter = 0.0f32;
+  let mut exit = f32::infinity;
+  let mut axis = -1;
+  let mut sign = 0.0f32;
+
+  for q in range(0u, 3u)
+  {
+    if dir[q].approx_eq(&0.0)
+    {
+      if origin[q] < min[q] || origin[q] > max[q]
+      { return None; }
+      continue;
+    }
+
+    let inv = 1.0 / dir[q];
+    let mut near = (min[q] - origin[q]) * inv;
+    let mut far = (max[q] - origin[q]) * inv;
+    /* Entering through the min face means a normal of -q. */
+    let mut face = -1.0f32;
+    if near > far
+    {
+      let tmp = near;
+      near = far;
+      far = tmp;
+      face = 1.0;
+    }
+
+    if near > enter
+    {
+      enter = near;
+      axis = q as int;
+      sign = face;
+    }
+    exit = cmp::min(exit, far);
+    if enter > exit
+    { return None; }
+  }
+
+  let mut normal = Vec3f::zero();
+  match axis
+  {
+    0 => { normal.x = sign; }
+    1 => { normal.y = sign; }
+    2 => { normal.z = sign; }
+    _ => { }
+  }
+  Some(Hit { distance: enter, normal: normal })
+}
+
+/* Starting inside the sphere is a hit where the ray leaves it. */
+pub fn ray_sphere(origin: &Vec3f, dir: &Vec3f, center: &Vec3f, radius: f32) -> Option<Hit>
+{
+  let offset = *origin - *center;
+  let a = dir.dot(dir);
+  let b = offset.dot(dir);
+  let c = offset.dot(&offset) - (radius * radius);
+  if a.approx_eq(&0.0)
+  { return None; }
+
+  let disc = (b * b) - (a * c);
+  if disc < 0.0
+  { return None; }
+
+  let root = disc.sqrt();
+  let mut t = (-b - root) / a;
+  if t < 0.0
+  { t = (-b + root) / a; }
+  if t < 0.0
+  { return None; }
+
+  let mut normal = (*origin + (*dir * t)) - *center;
+  normal.normalize();
+  if c < 0.0
+  { normal = -normal; } /* Facing back in, toward the origin. */
+  Some(Hit { distance: t, normal: normal })
+}
+
+/* Moller-Trumbore; both sides of the triangle are hit. */
+pub fn ray_triangle(origin: &Vec3f, dir: &Vec3f, v0: &Vec3f, v1: &Vec3f, v2: &Vec3f) -> Option<Hit>
+{
+  let e1 = *v1 - *v0;
+  let e2 = *v2 - *v0;
+  let p = dir.cross(&e2);
+  let det = e1.dot(&p);
+  if det.approx_eq(&0.0)
+  { return None; } /* Parallel, or a degenerate triangle. */
+  let inv_det = 1.0 / det;
+
+  let s = *origin - *v0;
+  let u = s.dot(&p) * inv_det;
+  if u < 0.0 || u > 1.0
+  { return None; }
+
+  let q = s.cross(&e1);
+  let v = dir.dot(&q) * inv_det;
+  if v < 0.0 || u + v > 1.0
+  { return None; }
+
+  let t = e2.dot(&q) * inv_det;
+  if t < 0.0
+  { return None; }
+
+  let mut normal = e1.cross(&e2);
+  normal.normalize();
+  if normal.dot(dir) > 0.0
+  { normal = -normal; }
+  Some(Hit { distance: t, normal: normal })
+}
+
+/* The distance is the fraction of the way from start to end. */
+pub fn segment_triangle(start: &Vec3f, end: &Vec3f, v0: &Vec3f, v1: &Vec3f, v2: &Vec3f) -> Option<Hit>
+{
+  match ray_triangle(start, &(*end - *start), v0, v1, v2)
+  {
+    Some(hit) if hit.distance <= 1.0 => Some(hit),
+    _ => None,
+  }
+}
+
+pub fn closest_point_aabb(point: &Vec3f, min: &Vec3f, max: &Vec3f) -> Vec3f
+{
+  Vec3f::new(point.x.clamp(&min.x, &max.x),
+             point.y.clamp(&min.y, &max.y),
+             point.z.clamp(&min.z, &max.z))
+}
+
+pub fn sphere_aabb(center: &Vec3f, radius: f32, min: &Vec3f, max: &Vec3f) -> Option<Hit>
+{
+  let closest = closest_point_aabb(center, min, max);
+  let offset = *center - closest;
+  let dist_sq = offset.dot(&offset);
+  if dist_sq > radius * radius
+  { return None; }
+
+  if dist_sq > 0.0
+  {
+    let dist = dist_sq.sqrt();
+    return Some(Hit { distance: radius - dist, normal: offset * (1.0 / dist) });
+  }
+
+  /* The center is inside the box; push out through the nearest face. */
+  let mut best = f32::infinity;
+  let mut normal = Vec3f::zero();
+  for q in range(0u, 3u)
+  {
+    for &side in [ -1.0f32, 1.0 ].iter()
+    {
+      let dist = if side < 0.0 { center[q] - min[q] } else { max[q] - center[q] };
+      if dist < best
+      {
+        best = dist;
+        normal = Vec3f::zero();
+        match q
+        {
+          0 => { normal.x = side; }
+          1 => { normal.y = side; }
+          _ => { normal.z = side; }
+        }
+      }
+    }
+  }
+  Some(Hit { distance: radius + best, normal: normal })
+}
+
+/* A capsule is the segment from start to end, swollen by radius. The
+ * distance from the segment to the box is convex along the segment,
+ * so its closest point is found by ternary search. */
+pub fn capsule_aabb(start: &Vec3f, end: &Vec3f, radius: f32, min: &Vec3f, max: &Vec3f) -> Option<Hit>
+{
+  let dir = *end - *start;
+  let dist_sq = |t: f32|
+  {
+    let p = *start + (dir * t);
+    let offset = p - closest_point_aabb(&p, min, max);
+    offset.dot(&offset)
+  };
+
+  let mut low = 0.0f32;
+  let mut high = 1.0f32;
+  for _ in range(0, 32)
+  {
+    let a = low + ((high - low) / 3.0);
+    let b = high - ((high - low) / 3.0);
+    if dist_sq(a) <= dist_sq(b)
+    { high = b; }
+    else
+    { low = a; }
+  }
+
+  sphere_aabb(&(*start + (dir * ((low + high) / 2.0))), radius, min, max)
+}
+
+/* Separating axis test between a triangle and a box, after
+ * Akenine-Moller's tribox3: the box's three axes, the triangle's
+ * normal, and the nine cross products of their edges. */
+pub fn tri_aabb(center: &Vec3f, half_extents: &Vec3f, v0: &Vec3f, v1: &Vec3f, v2: &Vec3f) -> bool
+{
+  /* Move everything so that the box's center is at the origin. */
+  let verts = [ *v0 - *center, *v1 - *center, *v2 - *center ];
+  let edges = [ verts[1] - verts[0], verts[2] - verts[1], verts[0] - verts[2] ];
+
+  let separated = |axis: &Vec3f|
+  {
+    let p0 = axis.dot(&verts[0]);
+    let p1 = axis.dot(&verts[1]);
+    let p2 = axis.dot(&verts[2]);
+    let rad = (half_extents.x * axis.x.abs()) +
+              (half_extents.y * axis.y.abs()) +
+              (half_extents.z * axis.z.abs());
+    cmp::min(p0, cmp::min(p1, p2)) > rad || cmp::max(p0, cmp::max(p1, p2)) < -rad
+  };
+
+  /* The edge cross products. */
+  let units = [ Vec3f::new(1.0, 0.0, 0.0), Vec3f::new(0.0, 1.0, 0.0), Vec3f::new(0.0, 0.0, 1.0) ];
+  for edge in edges.iter()
+  {
+    for unit in units.iter()
+    {
+      if separated(&unit.cross(edge))
+      { return false; }
+    }
+  }
+
+  /* The box's faces. */
+  for q in range(0u, 3u)
+  {
+    let low = cmp::min(verts[0][q], cmp::min(verts[1][q], verts[2][q]));
+    let high = cmp::max(verts[0][q], cmp::max(verts[1][q], verts[2][q]));
+    if low > half_extents[q] || high < -half_extents[q]
+    { return false; }
+  }
+
+  /* The triangle's plane. */
+  !separated(&edges[0].cross(&edges[1]))
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::{ ray_aabb, ray_sphere, ray_triangle, segment_triangle,
+               sphere_aabb, capsule_aabb, tri_aabb };
+  use super::super::Vec3f;
+
+  fn v(x: f32, y: f32, z: f32) -> Vec3f
+  { Vec3f::new(x, y, z) }
+
+  /* Loose enough for capsule_aabb's ternary search. */
+  fn near(a: f32, b: f32) -> bool
+  { (a - b).abs() < 0.001 }
+
+  fn close(a: &Vec3f, b: &Vec3f) -> bool
+  { near(a.x, b.x) && near(a.y, b.y) && near(a.z, b.z) }
+
+  /* A box from -1 to 1 on each axis. */
+  fn unit() -> (Vec3f, Vec3f)
+  { (v(-1.0, -1.0, -1.0), v(1.0, 1.0, 1.0)) }
+
+  #[test]
+  fn tri_aabb_touching()
+  {
+    let half = v(1.0, 1.0, 1.0);
+    /* Only a corner of the triangle touches a corner of the box. */
+    assert!(tri_aabb(&Vec3f::zero(), &half, &v(1.0, 1.0, 1.0), &v(3.0, 1.0, 2.0), &v(2.0, 3.0, 1.0)));
+    /* Only a corner of the triangle touches an edge of the box. */
+    assert!(tri_aabb(&Vec3f::zero(), &half, &v(1.0, 1.0, 0.0), &v(3.0, 1.0, 0.0), &v(1.0, 3.0, 0.0)));
+    /* The triangle's plane only touches the box's corner. */
+    assert!(tri_aabb(&Vec3f::zero(), &half, &v(3.0, 0.0, 0.0), &v(0.0, 3.0, 0.0), &v(0.0, 0.0, 3.0)));
+  }
+
+  #[test]
+  fn tri_aabb_separated()
+  {
+    let half = v(1.0, 1.0, 1.0);
+    /* By a face of the box. */
+    assert!(!tri_aabb(&Vec3f::zero(), &half, &v(1.01, 1.0, 0.0), &v(3.0, 1.0, 0.0), &v(1.01, 3.0, 0.0)));
+    /* Only by the triangle's plane, which passes just beyond the corner. */
+    assert!(!tri_aabb(&Vec3f::zero(), &half, &v(3.5, 0.0, 0.0), &v(0.0, 3.5, 0.0), &v(0.0, 0.0, 3.5)));
+    /* Only by an edge cross product; the triangle's plane cuts the
+     * box, but its near edge passes just beyond the box's edge. */
+    assert!(!tri_aabb(&Vec3f::zero(), &half, &v(2.1, 0.0, 0.0), &v(0.0, 2.1, 0.0), &v(5.0, 5.0, -20.0)));
+  }
+
+  #[test]
+  fn tri_aabb_overlapping()
+  {
+    /* A triangle much larger than the box, passing through its center. */
+    let half = v(1.0, 1.0, 1.0);
+    assert!(tri_aabb(&Vec3f::zero(), &half, &v(-10.0, 0.0, -10.0), &v(10.0, 0.0, -10.0), &v(0.0, 0.0, 10.0)));
+  }
+
+  #[test]
+  fn ray_aabb_hits()
+  {
+    let (min, max) = unit();
+
+    let hit = ray_aabb(&v(-5.0, 0.0, 0.0), &v(1.0, 0.0, 0.0), &min, &max).unwrap();
+    assert!(near(hit.distance, 4.0));
+    assert!(close(&hit.normal, &v(-1.0, 0.0, 0.0)));
+
+    /* From inside, the hit is immediate. */
+    let hit = ray_aabb(&Vec3f::zero(), &v(0.0, 1.0, 0.0), &min, &max).unwrap();
+    assert!(near(hit.distance, 0.0));
+    assert!(close(&hit.normal, &Vec3f::zero()));
+
+    assert!(ray_aabb(&v(-5.0, 2.0, 0.0), &v(1.0, 0.0, 0.0), &min, &max).is_none());
+    assert!(ray_aabb(&v(-5.0, 0.0, 0.0), &v(-1.0, 0.0, 0.0), &min, &max).is_none());
+  }
+
+  #[test]
+  fn ray_sphere_hits()
+  {
+    let hit = ray_sphere(&v(-5.0, 0.0, 0.0), &v(1.0, 0.0, 0.0), &Vec3f::zero(), 1.0).unwrap();
+    assert!(near(hit.distance, 4.0));
+    assert!(close(&hit.normal, &v(-1.0, 0.0, 0.0)));
+
+    /* From inside, the hit is where the ray leaves, facing back in. */
+    let hit = ray_sphere(&Vec3f::zero(), &v(1.0, 0.0, 0.0), &Vec3f::zero(), 1.0).unwrap();
+    assert!(near(hit.distance, 1.0));
+    assert!(close(&hit.normal, &v(-1.0, 0.0, 0.0)));
+
+    assert!(ray_sphere(&v(-5.0, 2.0, 0.0), &v(1.0, 0.0, 0.0), &Vec3f::zero(), 1.0).is_none());
+  }
+
+  #[test]
+  fn ray_triangle_hits()
+  {
+    let (v0, v1, v2) = (v(-1.0, -1.0, 0.0), v(1.0, -1.0, 0.0), v(0.0, 1.0, 0.0));
+
+    let hit = ray_triangle(&v(0.0, 0.0, 5.0), &v(0.0, 0.0, -1.0), &v0, &v1, &v2).unwrap();
+    assert!(near(hit.distance, 5.0));
+    assert!(close(&hit.normal, &v(0.0, 0.0, 1.0)));
+
+    /* The back is hit too, with the normal facing the ray. */
+    let hit = ray_triangle(&v(0.0, 0.0, -5.0), &v(0.0, 0.0, 1.0), &v0, &v1, &v2).unwrap();
+    assert!(close(&hit.normal, &v(0.0, 0.0, -1.0)));
+
+    assert!(ray_triangle(&v(2.0, 0.0, 5.0), &v(0.0, 0.0, -1.0), &v0, &v1, &v2).is_none());
+    assert!(segment_triangle(&v(0.0, 0.0, 5.0), &v(0.0, 0.0, 1.0), &v0, &v1, &v2).is_none());
+  }
+
+  #[test]
+  fn sphere_and_capsule_aabb()
+  {
+    let (min, max) = unit();
+
+    let hit = sphere_aabb(&v(1.5, 0.0, 0.0), 1.0, &min, &max).unwrap();
+    assert!(near(hit.distance, 0.5));
+    assert!(close(&hit.normal, &v(1.0, 0.0, 0.0)));
+    assert!(sphere_aabb(&v(3.0, 0.0, 0.0), 1.0, &min, &max).is_none());
+
+    assert!(capsule_aabb(&v(3.0, -5.0, 0.0), &v(3.0, 5.0, 0.0), 1.0, &min, &max).is_none());
+    let hit = capsule_aabb(&v(3.0, -5.0, 0.0), &v(3.0, 5.0, 0.0), 2.5, &min, &max).unwrap();
+    assert!(near(hit.distance, 0.5));
+    assert!(close(&hit.normal, &v(1.0, 0.0, 0.0)));
+  }
+}
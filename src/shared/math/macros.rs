@@ -0,0 +1,41 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/macros.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Macros shared between the vector types.
+*/
+
+/* A checked cast, which fails if any of the listed components
+ * doesn't survive the trip to the target's component type. */
+macro_rules! try_to
+(
+  ($Type:ident, $name:ident, $to:ident, $Target:ident, $($c:ident),+) =>
+  (
+    impl $Type
+    {
+      pub fn $name(&self) -> Option<super::$Target>
+      {
+        let v = self.$to();
+        if true $(&& (v.$c as f64) == (self.$c as f64))+
+        { Some(v) }
+        else
+        { None }
+      }
+    }
+  )
+)
+
+/* Casts to an integer type, clamped to its range first, since
+ * casting a float which is out of range is undefined. */
+macro_rules! saturate
+(
+  ($value:expr, $Target:ident) =>
+  (
+    (($value as f64).clamp(&(::std::$Target::min_value as f64),
+                           &(::std::$Target::max_value as f64))) as $Target
+  )
+)
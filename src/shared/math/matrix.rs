@@ -10,10 +10,14 @@
       for representing orientational data.
 */
 
-use std::ptr;
+use std::{ cmp, ptr };
 
 type Component = f32;
 
+/* How small a determinant can be, relative to the largest it could be
+ * for the matrix's rows, before the matrix is treated as singular. */
+static SINGULAR_EPSILON: Component = 0.000001;
+
 pub struct Mat4x4
 {
   data: [[f32, ..4], ..4]
@@ -163,6 +167,133 @@ impl Mat4x4
   pub fn get_position(&self) -> super::Vec3f
   { super::Vec3f::new(self.data[3][0], self.data[3][1], self.data[3][2]) }
 
+  /* Determinant of the whole matrix. */
+  pub fn determinant(&self) -> Component
+  {
+    let (s, c) = self.sub_determinants();
+    (s[0] * c[5]) - (s[1] * c[4]) + (s[2] * c[3]) + (s[3] * c[2]) - (s[4] * c[1]) + (s[5] * c[0])
+  }
+
+  pub fn transpose(&self) -> Mat4x4
+  {
+    let mut mat = Mat4x4::new();
+    for i in range(0, 4)
+    {
+      for k in range(0, 4)
+      { mat.data[i][k] = self.data[k][i]; }
+    }
+    mat
+  }
+
+  /* General inverse, by cofactors. None if the matrix is singular. */
+  pub fn inverse(&self) -> Option<Mat4x4>
+  {
+    let (s, c) = self.sub_determinants();
+    let det = (s[0] * c[5]) - (s[1] * c[4]) + (s[2] * c[3]) + (s[3] * c[2]) - (s[4] * c[1]) + (s[5] * c[0]);
+    if self.is_singular(det, 4)
+    { return None; }
+    let inv_det = 1.0 / det;
+
+    /* The inverse of the transpose is the transpose of the inverse,
+     * so it doesn't matter which way around data is read. */
+    let a = &self.data;
+    let mut mat = Mat4x4::new();
+    mat.data[0][0] = ( (a[1][1] * c[5]) - (a[1][2] * c[4]) + (a[1][3] * c[3])) * inv_det;
+    mat.data[0][1] = (-(a[0][1] * c[5]) + (a[0][2] * c[4]) - (a[0][3] * c[3])) * inv_det;
+    mat.data[0][2] = ( (a[3][1] * s[5]) - (a[3][2] * s[4]) + (a[3][3] * s[3])) * inv_det;
+    mat.data[0][3] = (-(a[2][1] * s[5]) + (a[2][2] * s[4]) - (a[2][3] * s[3])) * inv_det;
+
+    mat.data[1][0] = (-(a[1][0] * c[5]) + (a[1][2] * c[2]) - (a[1][3] * c[1])) * inv_det;
+    mat.data[1][1] = ( (a[0][0] * c[5]) - (a[0][2] * c[2]) + (a[0][3] * c[1])) * inv_det;
+    mat.data[1][2] = (-(a[3][0] * s[5]) + (a[3][2] * s[2]) - (a[3][3] * s[1])) * inv_det;
+    mat.data[1][3] = ( (a[2][0] * s[5]) - (a[2][2] * s[2]) + (a[2][3] * s[1])) * inv_det;
+
+    mat.data[2][0] = ( (a[1][0] * c[4]) - (a[1][1] * c[2]) + (a[1][3] * c[0])) * inv_det;
+    mat.data[2][1] = (-(a[0][0] * c[4]) + (a[0][1] * c[2]) - (a[0][3] * c[0])) * inv_det;
+    mat.data[2][2] = ( (a[3][0] * s[4]) - (a[3][1] * s[2]) + (a[3][3] * s[0])) * inv_det;
+    mat.data[2][3] = (-(a[2][0] * s[4]) + (a[2][1] * s[2]) - (a[2][3] * s[0])) * inv_det;
+
+    mat.data[3][0] = (-(a[1][0] * c[3]) + (a[1][1] * c[1]) - (a[1][2] * c[0])) * inv_det;
+    mat.data[3][1] = ( (a[0][0] * c[3]) - (a[0][1] * c[1]) + (a[0][2] * c[0])) * inv_det;
+    mat.data[3][2] = (-(a[3][0] * s[3]) + (a[3][1] * s[1]) - (a[3][2] * s[0])) * inv_det;
+    mat.data[3][3] = ( (a[2][0] * s[3]) - (a[2][1] * s[1]) + (a[2][2] * s[0])) * inv_det;
+
+    Some(mat)
+  }
+
+  /* Cheaper inverse for matrices whose bottom row is (0, 0, 0, 1),
+   * such as any mix of scales, rotations, and translations. None if
+   * the 3x3 part is singular. */
+  pub fn affine_inverse(&self) -> Option<Mat4x4>
+  {
+    let a = &self.data;
+    let c0 = (a[1][1] * a[2][2]) - (a[1][2] * a[2][1]);
+    let c1 = (a[1][2] * a[2][0]) - (a[1][0] * a[2][2]);
+    let c2 = (a[1][0] * a[2][1]) - (a[1][1] * a[2][0]);
+    let det = (a[0][0] * c0) + (a[0][1] * c1) + (a[0][2] * c2);
+    if self.is_singular(det, 3)
+    { return None; }
+    let inv_det = 1.0 / det;
+
+    let mut mat = Mat4x4::new();
+    mat.data[0][0] = c0 * inv_det;
+    mat.data[0][1] = ((a[0][2] * a[2][1]) - (a[0][1] * a[2][2])) * inv_det;
+    mat.data[0][2] = ((a[0][1] * a[1][2]) - (a[0][2] * a[1][1])) * inv_det;
+    mat.data[1][0] = c1 * inv_det;
+    mat.data[1][1] = ((a[0][0] * a[2][2]) - (a[0][2] * a[2][0])) * inv_det;
+    mat.data[1][2] = ((a[0][2] * a[1][0]) - (a[0][0] * a[1][2])) * inv_det;
+    mat.data[2][0] = c2 * inv_det;
+    mat.data[2][1] = ((a[0][1] * a[2][0]) - (a[0][0] * a[2][1])) * inv_det;
+    mat.data[2][2] = ((a[0][0] * a[1][1]) - (a[0][1] * a[1][0])) * inv_det;
+
+    /* The translation is undone after the rest. */
+    let t = mat.transform_dir(&self.get_position());
+    mat.data[3][0] = -t.x;
+    mat.data[3][1] = -t.y;
+    mat.data[3][2] = -t.z;
+
+    Some(mat)
+  }
+
+  pub fn mul_vec4(&self, vec: &super::Vec4f) -> super::Vec4f
+  {
+    let d = &self.data;
+    super::Vec4f::new((d[0][0] * vec.x) + (d[1][0] * vec.y) + (d[2][0] * vec.z) + (d[3][0] * vec.w),
+                      (d[0][1] * vec.x) + (d[1][1] * vec.y) + (d[2][1] * vec.z) + (d[3][1] * vec.w),
+                      (d[0][2] * vec.x) + (d[1][2] * vec.y) + (d[2][2] * vec.z) + (d[3][2] * vec.w),
+                      (d[0][3] * vec.x) + (d[1][3] * vec.y) + (d[2][3] * vec.z) + (d[3][3] * vec.w))
+  }
+
+  /* Transforms a position (w = 1), dividing through by the resulting w
+   * so that projections, and their inverses, work as expected. */
+  pub fn transform_point(&self, point: &super::Vec3f) -> super::Vec3f
+  {
+    let v = self.mul_vec4(&super::Vec4f::new(point.x, point.y, point.z, 1.0));
+    if v.w.approx_eq(&1.0) || v.w.approx_eq(&0.0)
+    { super::Vec3f::new(v.x, v.y, v.z) }
+    else
+    { super::Vec3f::new(v.x / v.w, v.y / v.w, v.z / v.w) }
+  }
+
+  /* Transforms a direction (w = 0); translation doesn't apply. For
+   * normals under non-uniform scale, use the inverse transpose. */
+  pub fn transform_dir(&self, dir: &super::Vec3f) -> super::Vec3f
+  {
+    let d = &self.data;
+    super::Vec3f::new((d[0][0] * dir.x) + (d[1][0] * dir.y) + (d[2][0] * dir.z),
+                      (d[0][1] * dir.x) + (d[1][1] * dir.y) + (d[2][1] * dir.z),
+                      (d[0][2] * dir.x) + (d[1][2] * dir.y) + (d[2][2] * dir.z))
+  }
+
+  /* The largest stretch applied along any axis, for scaling radii. */
+  pub fn max_scale(&self) -> Component
+  {
+    let x = self.transform_dir(&super::Vec3f::new(1.0, 0.0, 0.0)).length();
+    let y = self.transform_dir(&super::Vec3f::new(0.0, 1.0, 0.0)).length();
+    let z = self.transform_dir(&super::Vec3f::new(0.0, 0.0, 1.0)).length();
+    cmp::max(x, cmp::max(y, z))
+  }
+
   pub fn identity(&mut self)
   {
     self.data = [ [1.0, 0.0, 0.0, 0.0],
@@ -175,6 +306,45 @@ impl Mat4x4
   { ptr::to_unsafe_ptr(self) }
 }
 
+impl Mat4x4
+{
+  /* The 2x2 determinants of the top two and bottom two rows of
+   * data, shared by determinant and inverse. */
+  fn sub_determinants(&self) -> ([Component, ..6], [Component, ..6])
+  {
+    let a = &self.data;
+    ([ (a[0][0] * a[1][1]) - (a[1][0] * a[0][1]),
+       (a[0][0] * a[1][2]) - (a[1][0] * a[0][2]),
+       (a[0][0] * a[1][3]) - (a[1][0] * a[0][3]),
+       (a[0][1] * a[1][2]) - (a[1][1] * a[0][2]),
+       (a[0][1] * a[1][3]) - (a[1][1] * a[0][3]),
+       (a[0][2] * a[1][3]) - (a[1][2] * a[0][3]) ],
+     [ (a[2][0] * a[3][1]) - (a[3][0] * a[2][1]),
+       (a[2][0] * a[3][2]) - (a[3][0] * a[2][2]),
+       (a[2][0] * a[3][3]) - (a[3][0] * a[2][3]),
+       (a[2][1] * a[3][2]) - (a[3][1] * a[2][2]),
+       (a[2][1] * a[3][3]) - (a[3][1] * a[2][3]),
+       (a[2][2] * a[3][3]) - (a[3][2] * a[2][3]) ])
+  }
+
+  /* Whether the determinant of the top-left size x size part is
+   * negligible next to Hadamard's bound, the product of its rows'
+   * lengths. Being relative, matrices which are merely small, such
+   * as a uniform scale of 0.005, aren't mistaken for singular. */
+  fn is_singular(&self, det: Component, size: uint) -> bool
+  {
+    let mut bound = 1.0;
+    for i in range(0, size)
+    {
+      let mut len_sq = 0.0;
+      for k in range(0, size)
+      { len_sq += self.data[i][k] * self.data[i][k]; }
+      bound *= len_sq.sqrt();
+    }
+    !(det.abs() > bound * SINGULAR_EPSILON)
+  }
+}
+
 /***** Operator Overloads *****/
 impl Mul<Mat4x4, Mat4x4> for Mat4x4
 {
@@ -197,3 +367,135 @@ impl Mul<Mat4x4, Mat4x4> for Mat4x4
   }
 }
 
+#[cfg(test)]
+mod test
+{
+  use super::Mat4x4;
+  use super::super::Vec3f;
+
+  fn near(a: f32, b: f32) -> bool
+  { (a - b).abs() < 0.0001 }
+
+  fn is_identity(mat: &Mat4x4) -> bool
+  {
+    for i in range(0u, 4u)
+    {
+      for k in range(0u, 4u)
+      {
+        if !near(mat.data[i][k], if i == k { 1.0 } else { 0.0 })
+        { return false; }
+      }
+    }
+    true
+  }
+
+  fn close(a: &Vec3f, b: &Vec3f) -> bool
+  { near(a.x, b.x) && near(a.y, b.y) && near(a.z, b.z) }
+
+  /* Scale, then rotate, then translate. */
+  fn composite() -> Mat4x4
+  { Mat4x4::new_scale(2.0, 3.0, 4.0) * Mat4x4::new_rotation_y(30.0) * Mat4x4::new_translation(1.0, 2.0, 3.0) }
+
+  #[test]
+  fn determinant()
+  {
+    assert!(near(Mat4x4::new().determinant(), 1.0));
+    assert!(near(Mat4x4::new_scale(2.0, 3.0, 4.0).determinant(), 24.0));
+    assert!(near(Mat4x4::new_translation(5.0, 6.0, 7.0).determinant(), 1.0));
+    assert!(near(Mat4x4::new_rotation_z(40.0).determinant(), 1.0));
+
+    /* Triangular, so it's the product of the diagonal. */
+    let mat = Mat4x4 { data: [ [ 2.0, 0.0, 0.0, 0.0 ],
+                               [ 1.0, 3.0, 0.0, 0.0 ],
+                               [ 4.0, 5.0, 6.0, 0.0 ],
+                               [ 7.0, 8.0, 9.0, 1.0 ] ] };
+    assert!(near(mat.determinant(), 36.0));
+    assert!(near(mat.transpose().determinant(), 36.0));
+  }
+
+  #[test]
+  fn inverse()
+  {
+    let mat = composite();
+    let inv = mat.inverse().unwrap();
+    assert!(is_identity(&(mat * inv)));
+    assert!(is_identity(&(inv * mat)));
+
+    /* The affine inverse agrees wherever it applies. */
+    let affine = mat.affine_inverse().unwrap();
+    for i in range(0u, 4u)
+    {
+      for k in range(0u, 4u)
+      { assert!(near(inv.data[i][k], affine.data[i][k])); }
+    }
+
+    /* Projections aren't affine, but are invertible. */
+    let proj = Mat4x4::new_perspective(90.0, 1.5, 0.1, 100.0);
+    assert!(is_identity(&(proj * proj.inverse().unwrap())));
+  }
+
+  #[test]
+  fn small_but_invertible()
+  {
+    /* The determinant is around 1e-7, but the matrix is fine. */
+    let mat = Mat4x4::new_scale(0.005, 0.005, 0.005);
+    let inv = mat.inverse().unwrap();
+    assert!(near(inv.data[0][0], 200.0));
+    assert!(is_identity(&(mat * inv)));
+    assert!(near(mat.affine_inverse().unwrap().data[2][2], 200.0));
+  }
+
+  #[test]
+  fn singular()
+  {
+    let flat = Mat4x4::new_scale(1.0, 0.0, 1.0);
+    assert!(flat.inverse().is_none());
+    assert!(flat.affine_inverse().is_none());
+
+    /* Two rows the same, off of the axes. */
+    let mut mat = composite();
+    mat.data[1] = mat.data[0];
+    assert!(mat.inverse().is_none());
+    assert!(mat.affine_inverse().is_none());
+  }
+
+  #[test]
+  fn transpose()
+  {
+    let mat = composite();
+    let trans = mat.transpose();
+    for i in range(0u, 4u)
+    {
+      for k in range(0u, 4u)
+      {
+        assert!(trans.data[i][k] == mat.data[k][i]);
+        assert!(trans.transpose().data[i][k] == mat.data[i][k]);
+      }
+    }
+  }
+
+  #[test]
+  fn transform()
+  {
+    let one = Vec3f::new(1.0, 1.0, 1.0);
+    let translation = Mat4x4::new_translation(1.0, 2.0, 3.0);
+    let scale = Mat4x4::new_scale(2.0, 3.0, 4.0);
+
+    assert!(close(&translation.transform_point(&one), &Vec3f::new(2.0, 3.0, 4.0)));
+    assert!(close(&translation.transform_dir(&one), &one));
+    assert!(close(&scale.transform_point(&one), &Vec3f::new(2.0, 3.0, 4.0)));
+    assert!(close(&scale.transform_dir(&one), &Vec3f::new(2.0, 3.0, 4.0)));
+
+    /* Scaled first, then moved. */
+    let both = scale * translation;
+    assert!(close(&both.transform_point(&one), &Vec3f::new(3.0, 5.0, 7.0)));
+    assert!(close(&both.transform_dir(&one), &Vec3f::new(2.0, 3.0, 4.0)));
+
+    /* Points are divided through by w; the center of the near
+     * plane lands on the near side of clip space. */
+    let proj = Mat4x4::new_perspective(90.0, 1.5, 0.1, 100.0);
+    let clip = proj.transform_point(&Vec3f::new(0.0, 0.0, -0.1));
+    assert!(close(&clip, &Vec3f::new(0.0, 0.0, -1.0)));
+    assert!(close(&proj.inverse().unwrap().transform_point(&clip), &Vec3f::new(0.0, 0.0, -0.1)));
+  }
+}
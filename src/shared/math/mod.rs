@@ -24,7 +24,18 @@ pub use self::vec2::*;
 pub use self::vec3::*;
 pub use self::vec4::*;
 pub use self::bb3::*;
+pub use self::frustum::*;
+pub use self::plane::*;
+pub use self::ray::*;
+pub use self::sphere::*;
+pub use self::obb::*;
+pub use self::capsule::*;
+pub use self::fixed::*;
 pub use self::util::*;
+pub use self::random::Random;
+
+#[macro_escape]
+mod macros;
 
 pub mod quaternion;
 pub mod matrix;
@@ -32,5 +43,15 @@ pub mod vec2;
 pub mod vec3;
 pub mod vec4;
 pub mod bb3;
+pub mod frustum;
+pub mod plane;
+pub mod ray;
+pub mod sphere;
+pub mod obb;
+pub mod capsule;
+pub mod intersect;
+pub mod curve;
+pub mod fixed;
 pub mod util;
+pub mod random;
 
@@ -0,0 +1,153 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/obb.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      An oriented bounding box, as a center,
+      three orthonormal axes, and the half size
+      along each of them.
+*/
+
+pub struct OBB
+{
+  center: super::Vec3f,
+  axes: [super::Vec3f, ..3],
+  half_extents: super::Vec3f,
+}
+
+impl OBB
+{
+  pub fn new(center: super::Vec3f, axes: [super::Vec3f, ..3], half_extents: super::Vec3f) -> OBB
+  { OBB { center: center, axes: axes, half_extents: half_extents } }
+
+  pub fn new_from_rotation(center: super::Vec3f, rotation: &super::Quaternion,
+                           half_extents: super::Vec3f) -> OBB
+  {
+    OBB
+    {
+      center: center,
+      axes: [ rotation.rotate_vec(&super::Vec3f::new(1.0, 0.0, 0.0)),
+              rotation.rotate_vec(&super::Vec3f::new(0.0, 1.0, 0.0)),
+              rotation.rotate_vec(&super::Vec3f::new(0.0, 0.0, 1.0)) ],
+      half_extents: half_extents,
+    }
+  }
+
+  pub fn new_from_aabb(bb: &super::BB3) -> OBB
+  {
+    OBB
+    {
+      center: bb.center(),
+      axes: [ super::Vec3f::new(1.0, 0.0, 0.0),
+              super::Vec3f::new(0.0, 1.0, 0.0),
+              super::Vec3f::new(0.0, 0.0, 1.0) ],
+      half_extents: bb.extents(),
+    }
+  }
+
+  /* Into the box's frame, where it spans -half_extents to half_extents. */
+  pub fn to_local(&self, point: &super::Vec3f) -> super::Vec3f
+  { self.to_local_dir(&(*point - self.center)) }
+
+  pub fn to_local_dir(&self, dir: &super::Vec3f) -> super::Vec3f
+  { super::Vec3f::new(self.axes[0].dot(dir), self.axes[1].dot(dir), self.axes[2].dot(dir)) }
+
+  pub fn to_world(&self, point: &super::Vec3f) -> super::Vec3f
+  { self.center + self.to_world_dir(point) }
+
+  pub fn to_world_dir(&self, dir: &super::Vec3f) -> super::Vec3f
+  { (self.axes[0] * dir.x) + (self.axes[1] * dir.y) + (self.axes[2] * dir.z) }
+
+  /* Inclusive of the box's faces. */
+  pub fn contains(&self, point: &super::Vec3f) -> bool
+  {
+    let local = self.to_local(point);
+    local.x.abs() <= self.half_extents.x &&
+    local.y.abs() <= self.half_extents.y &&
+    local.z.abs() <= self.half_extents.z
+  }
+
+  pub fn closest_point(&self, point: &super::Vec3f) -> super::Vec3f
+  { self.to_world(&self.to_local(point).clamp(&-self.half_extents, &self.half_extents)) }
+
+  /* Indexed by bits, as with BB3::corners: 1 picks the positive
+   * end of the first axis, 2 the second, and 4 the third. */
+  pub fn corners(&self) -> [super::Vec3f, ..8]
+  {
+    let h = self.half_extents;
+    let mut corners = [super::Vec3f::zero(), ..8];
+    for i in range(0u, 8u)
+    {
+      corners[i] = self.to_world(&super::Vec3f::new(if i & 1 == 0 { -h.x } else { h.x },
+                                                    if i & 2 == 0 { -h.y } else { h.y },
+                                                    if i & 4 == 0 { -h.z } else { h.z }));
+    }
+    corners
+  }
+
+  /* The axis-aligned box around this one. */
+  pub fn bounds(&self) -> super::BB3
+  {
+    let corners = self.corners();
+    super::BB3::from_points(corners.as_slice())
+  }
+
+  /* Separating axis test: each box's three axes and the nine cross
+   * products between them. */
+  pub fn intersects_obb(&self, other: &OBB) -> bool
+  {
+    let offset = other.center - self.center;
+    let radius = |obb: &OBB, axis: &super::Vec3f|
+    {
+      (obb.half_extents.x * obb.axes[0].dot(axis).abs()) +
+      (obb.half_extents.y * obb.axes[1].dot(axis).abs()) +
+      (obb.half_extents.z * obb.axes[2].dot(axis).abs())
+    };
+
+    let mut axes = ~[];
+    for i in range(0u, 3u)
+    {
+      axes.push(self.axes[i]);
+      axes.push(other.axes[i]);
+      for j in range(0u, 3u)
+      { axes.push(self.axes[i].cross(&other.axes[j])); }
+    }
+
+    for axis in axes.iter()
+    {
+      /* Parallel edges give no axis; the face axes cover them. */
+      if axis.dot(axis) < 0.000001
+      { continue; }
+      if offset.dot(axis).abs() > radius(self, axis) + radius(other, axis)
+      { return false; }
+    }
+    true
+  }
+
+  /* Scale is pulled out of the transformed axes into the half
+   * extents. Shear leaves the axes no longer orthogonal. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> OBB
+  {
+    let mut axes = [super::Vec3f::zero(), ..3];
+    let mut half = [0.0f32, ..3];
+    for i in range(0u, 3u)
+    {
+      let axis = mat.transform_dir(&(self.axes[i] * self.half_extents[i]));
+      half[i] = axis.length();
+      axes[i] = if half[i].approx_eq(&0.0)
+      { mat.transform_dir(&self.axes[i]) }
+      else
+      { axis * (1.0 / half[i]) };
+    }
+
+    OBB
+    {
+      center: mat.transform_point(&self.center),
+      axes: axes,
+      half_extents: super::Vec3f::new(half[0], half[1], half[2]),
+    }
+  }
+}
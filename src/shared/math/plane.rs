@@ -0,0 +1,116 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/plane.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A plane, as the points p for which
+      dot(normal, p) + distance = 0, matching
+      math::intersect and the frustum's planes.
+*/
+
+/* How close to the plane a point must be to be on it. */
+static ON_PLANE_EPSILON: f32 = 0.0001;
+
+pub enum Plane_Side
+{
+  Front_Side,
+  Back_Side,
+  On_Plane,
+}
+
+pub struct Plane
+{
+  normal: super::Vec3f,
+  distance: f32,
+}
+
+impl Plane
+{
+  /* The normal is normalized, and the distance scaled along with
+   * it, so that it's the same plane. A zero normal is left as is. */
+  pub fn new(normal: super::Vec3f, distance: f32) -> Plane
+  {
+    let len = normal.length();
+    if len.approx_eq(&0.0)
+    { return Plane { normal: normal, distance: distance }; }
+
+    Plane { normal: normal * (1.0 / len), distance: distance / len }
+  }
+
+  /* The normal is normalized. */
+  pub fn new_from_normal_point(normal: &super::Vec3f, point: &super::Vec3f) -> Plane
+  {
+    let n = super::Vec3f::new_normalized(normal);
+    Plane { normal: n, distance: -n.dot(point) }
+  }
+
+  /* Counter-clockwise points face the viewer. A degenerate
+   * triangle gives a zero normal. */
+  pub fn new_from_points(a: &super::Vec3f, b: &super::Vec3f, c: &super::Vec3f) -> Plane
+  { Plane::new_from_normal_point(&(*b - *a).cross(&(*c - *a)), a) }
+
+  /* Positive in front of the plane, negative behind. */
+  pub fn distance_to(&self, point: &super::Vec3f) -> f32
+  { self.normal.dot(point) + self.distance }
+
+  /* The closest point on the plane. */
+  pub fn project(&self, point: &super::Vec3f) -> super::Vec3f
+  { *point - (self.normal * self.distance_to(point)) }
+
+  pub fn classify(&self, point: &super::Vec3f) -> Plane_Side
+  {
+    let dist = self.distance_to(point);
+    if dist > ON_PLANE_EPSILON
+    { Front_Side }
+    else if dist < -ON_PLANE_EPSILON
+    { Back_Side }
+    else
+    { On_Plane }
+  }
+
+  /* The same plane, facing the other way. */
+  pub fn flip(&self) -> Plane
+  { Plane { normal: -self.normal, distance: -self.distance } }
+
+  /* The normal goes through the inverse transpose, so non-uniform
+   * scale keeps it perpendicular. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> Plane
+  {
+    let point = mat.transform_point(&(self.normal * -self.distance));
+    let normal = match mat.inverse()
+    {
+      Some(inv) => inv.transpose().transform_dir(&self.normal),
+      None => mat.transform_dir(&self.normal),
+    };
+    Plane::new_from_normal_point(&normal, &point)
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::Plane;
+  use super::super::{ Vec3f, Mat4x4 };
+
+  #[test]
+  fn unnormalized_input()
+  {
+    /* The plane y = 2, with a normal four units long. */
+    let plane = Plane::new(Vec3f::new(0.0, 4.0, 0.0), -8.0);
+    assert!(plane.normal == Vec3f::new(0.0, 1.0, 0.0));
+    assert!(plane.distance_to(&Vec3f::new(1.0, 5.0, 3.0)).approx_eq(&3.0));
+    assert!(plane.project(&Vec3f::new(1.0, 5.0, 3.0)) == Vec3f::new(1.0, 2.0, 3.0));
+
+    /* Moved up by one, it's the plane y = 3. */
+    let moved = plane.transform(&Mat4x4::new_translation(0.0, 1.0, 0.0));
+    assert!(moved.distance_to(&Vec3f::new(7.0, 3.0, -2.0)).approx_eq(&0.0));
+    assert!(moved.normal == Vec3f::new(0.0, 1.0, 0.0));
+
+    /* Stretched to twice the height, it's the plane y = 4. */
+    let scaled = plane.transform(&Mat4x4::new_scale(1.0, 2.0, 1.0));
+    assert!(scaled.distance_to(&Vec3f::new(7.0, 4.0, -2.0)).approx_eq(&0.0));
+  }
+}
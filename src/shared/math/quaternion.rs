@@ -34,15 +34,79 @@ impl Quaternion
   pub fn zero() -> Quaternion
   { Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 0.0 } }
 
+  /* No rotation at all. */
+  pub fn identity() -> Quaternion
+  { Quaternion { x: 0.0, y: 0.0, z: 0.0, w: 1.0 } }
+
+  /* A rotation of angle radians about the axis. */
   pub fn new_from_axis(axis: &super::Vec3f, angle: f32) -> Quaternion
   {
-    let sin_angle = angle.sin();
+    let sin_angle = (angle / 2.0).sin();
     let norm_axis = super::Vec3f::new_normalized(axis);
 
     Quaternion {  x: (norm_axis.x * sin_angle),
                   y: (norm_axis.y * sin_angle),
                   z: (norm_axis.z * sin_angle),
-                  w: angle.cos() }
+                  w: (angle / 2.0).cos() }
+  }
+
+  /* The inverse of to_mat; only the rotation in the upper 3x3 is
+   * used, and it's expected to be orthonormal. */
+  pub fn new_from_mat(mat: &super::Mat4x4) -> Quaternion
+  {
+    let d = &mat.data;
+    Quaternion::new_from_rotation([ [ d[0][0], d[0][1], d[0][2] ],
+                                    [ d[1][0], d[1][1], d[1][2] ],
+                                    [ d[2][0], d[2][1], d[2][2] ] ])
+  }
+
+  /* The rotation which turns +Z to face forward, with +Y as
+   * close to up as it can get, so that it can be used as the
+   * camera's orientation. Forward and up can't be parallel. */
+  pub fn new_look_rotation(forward: &super::Vec3f, up: &super::Vec3f) -> Quaternion
+  {
+    let f = super::Vec3f::new_normalized(forward);
+    let side = super::Vec3f::new_normalized(&up.cross(&f));
+    let proper_up = f.cross(&side);
+
+    /* The rotated axes are the columns. */
+    Quaternion::new_from_rotation([ [ side.x, proper_up.x, f.x ],
+                                    [ side.y, proper_up.y, f.y ],
+                                    [ side.z, proper_up.z, f.z ] ])
+  }
+
+  /* From a rotation matrix, indexed [row][column], by whichever of
+   * w, x, y, or z is largest, to stay clear of dividing by zero. */
+  fn new_from_rotation(m: [[f32, ..3], ..3]) -> Quaternion
+  {
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let mut q = if trace > 0.0
+    {
+      let s = (trace + 1.0).sqrt() * 2.0; /* 4w */
+      Quaternion::new((m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s,
+                      (m[1][0] - m[0][1]) / s, s / 4.0)
+    }
+    else if m[0][0] > m[1][1] && m[0][0] > m[2][2]
+    {
+      let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0; /* 4x */
+      Quaternion::new(s / 4.0, (m[0][1] + m[1][0]) / s,
+                      (m[0][2] + m[2][0]) / s, (m[2][1] - m[1][2]) / s)
+    }
+    else if m[1][1] > m[2][2]
+    {
+      let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0; /* 4y */
+      Quaternion::new((m[0][1] + m[1][0]) / s, s / 4.0,
+                      (m[1][2] + m[2][1]) / s, (m[0][2] - m[2][0]) / s)
+    }
+    else
+    {
+      let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0; /* 4z */
+      Quaternion::new((m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s,
+                      s / 4.0, (m[1][0] - m[0][1]) / s)
+    };
+    q.normalize();
+
+    q
   }
 
   pub fn new_from_euler(yaw: f32, pitch: f32, roll: f32) -> Quaternion
@@ -73,8 +137,41 @@ impl Quaternion
   pub fn new_slerp(lhs: &Quaternion, rhs: &Quaternion, interp: f32) -> Quaternion
   { lhs.slerp(rhs, interp) }
 
+  pub fn new_nlerp(lhs: &Quaternion, rhs: &Quaternion, interp: f32) -> Quaternion
+  { lhs.nlerp(rhs, interp) }
+
   pub fn get_conjugate(&self) -> Quaternion
-  { Quaternion { x: -self.x, y: -self.y, z: -self.z, w: -self.w } }
+  { Quaternion { x: -self.x, y: -self.y, z: -self.z, w: self.w } }
+
+  /* Works for quaternions of any length; the inverse of
+   * zero is zero. For unit quaternions, it's the conjugate. */
+  pub fn inverse(&self) -> Quaternion
+  {
+    let len_sq = self.dot(self);
+    if len_sq.approx_eq(&0.0)
+    { return Quaternion::zero(); }
+
+    let mut inv = self.get_conjugate();
+    inv.scale(1.0 / len_sq);
+    inv
+  }
+
+  /* The normalized axis and the angle, in radians, of the rotation.
+   * With (next to) no rotation, the axis is arbitrarily +X. */
+  pub fn to_axis_angle(&self) -> (super::Vec3f, f32)
+  {
+    let mut q = *self;
+    q.normalize();
+    if q.w < 0.0
+    { q.scale(-1.0); } /* Take the short way around. */
+
+    let sin_half = (1.0 - (q.w * q.w)).sqrt();
+    if sin_half.approx_eq(&0.0)
+    { return (super::Vec3f::new(1.0, 0.0, 0.0), 0.0); }
+
+    (super::Vec3f::new(q.x / sin_half, q.y / sin_half, q.z / sin_half),
+     2.0 * q.w.clamp(&-1.0, &1.0).acos())
+  }
 
   pub fn normalize(&mut self)
   {
@@ -110,11 +207,9 @@ impl Quaternion
     { 1.0 };
     cos_omega *= scale;
 
-    let mut ret = *_rhs;
-    ret.scale(scale);
-
-    /* We should have to unit quaternions. */
-    assert!(cos_omega < 1.1);
+    /* Inputs which aren't quite unit length can push this past
+     * one; anything that close is lerped below anyway. */
+    cos_omega = cmp::min(cos_omega, 1.0);
 
     let scale0;
     let scale1;
@@ -145,6 +240,97 @@ impl Quaternion
     )
   }
 
+  /* Linear interpolation, renormalized. Cheaper than slerp, at the
+   * cost of a non-constant angular velocity, which is negligible
+   * for the small steps between animation frames. */
+  pub fn nlerp(&self, rhs: &Quaternion, interp: f32) -> Quaternion
+  {
+    let t = interp.clamp(&0.0, &1.0);
+    let sign = if self.dot(rhs) < 0.0 { -1.0 } else { 1.0 };
+
+    let mut q = Quaternion::new
+    (
+      (self.x * (1.0 - t)) + (rhs.x * sign * t),
+      (self.y * (1.0 - t)) + (rhs.y * sign * t),
+      (self.z * (1.0 - t)) + (rhs.z * sign * t),
+      (self.w * (1.0 - t)) + (rhs.w * sign * t)
+    );
+    q.normalize();
+
+    q
+  }
+
+  /* Spherical cubic interpolation from self to rhs, through the
+   * control points a and b, which come from squad_control. Chaining
+   * these through a sequence of keys is smooth across each key. */
+  pub fn squad(&self, rhs: &Quaternion, a: &Quaternion, b: &Quaternion, interp: f32) -> Quaternion
+  {
+    let t = interp.clamp(&0.0, &1.0);
+    let outer = self.slerp_no_invert(rhs, t);
+    let inner = a.slerp_no_invert(b, t);
+    outer.slerp_no_invert(&inner, 2.0 * t * (1.0 - t))
+  }
+
+  /* The squad control point for curr, between prev and next. */
+  pub fn squad_control(prev: &Quaternion, curr: &Quaternion, next: &Quaternion) -> Quaternion
+  {
+    let inv = curr.inverse();
+    let to_next = (inv * *next).log();
+    let to_prev = (inv * *prev).log();
+    let sum = Quaternion::new(to_next.x + to_prev.x, to_next.y + to_prev.y, to_next.z + to_prev.z, 0.0);
+
+    let mut step = Quaternion::new(sum.x * -0.25, sum.y * -0.25, sum.z * -0.25, 0.0).exp();
+    step = *curr * step;
+    step.normalize();
+
+    step
+  }
+
+  /* Slerp without taking the short way around, which squad
+   * needs so that its curves stay continuous. */
+  fn slerp_no_invert(&self, rhs: &Quaternion, interp: f32) -> Quaternion
+  {
+    let cos_omega = self.dot(rhs).clamp(&-1.0, &1.0);
+    if cos_omega.abs() > 0.9999
+    { return self.nlerp(rhs, interp); }
+
+    let omega = cos_omega.acos();
+    let one_over_sin_omega = 1.0 / omega.sin();
+    let scale0 = ((1.0 - interp) * omega).sin() * one_over_sin_omega;
+    let scale1 = (interp * omega).sin() * one_over_sin_omega;
+
+    Quaternion::new
+    (
+      (scale0 * self.x) + (scale1 * rhs.x),
+      (scale0 * self.y) + (scale1 * rhs.y),
+      (scale0 * self.z) + (scale1 * rhs.z),
+      (scale0 * self.w) + (scale1 * rhs.w)
+    )
+  }
+
+  /* Log of a unit quaternion: (axis * half angle, 0). */
+  fn log(&self) -> Quaternion
+  {
+    let sin_half = ((self.x * self.x) + (self.y * self.y) + (self.z * self.z)).sqrt();
+    if sin_half.approx_eq(&0.0)
+    { return Quaternion::zero(); }
+
+    let half = sin_half.atan2(&self.w);
+    let scale = half / sin_half;
+    Quaternion::new(self.x * scale, self.y * scale, self.z * scale, 0.0)
+  }
+
+  /* Exp of a pure quaternion (w = 0); the inverse of log. */
+  fn exp(&self) -> Quaternion
+  {
+    let half = ((self.x * self.x) + (self.y * self.y) + (self.z * self.z)).sqrt();
+    if half.approx_eq(&0.0)
+    { return Quaternion::identity(); }
+
+    let scale = half.sin() / half;
+    Quaternion::new(self.x * scale, self.y * scale, self.z * scale, half.cos())
+  }
+
   pub fn compute_w(&mut self)
   {
     let t = 1.0 - (self.x * self.x) - (self.y * self.y) - (self.z * self.z);
@@ -256,3 +442,126 @@ impl cmp::TotalEq for Quaternion
   { self == other }
 }
 
+
+#[cfg(test)]
+mod test
+{
+  use std::f32;
+  use super::Quaternion;
+  use super::super::Vec3f;
+
+  /* Trig chains drift further than approx_eq allows. */
+  fn near(a: f32, b: f32) -> bool
+  { (a - b).abs() < 0.0001 }
+
+  fn close(a: &Quaternion, b: &Quaternion) -> bool
+  { near(a.x, b.x) && near(a.y, b.y) && near(a.z, b.z) && near(a.w, b.w) }
+
+  fn close_vec(a: &Vec3f, b: &Vec3f) -> bool
+  { near(a.x, b.x) && near(a.y, b.y) && near(a.z, b.z) }
+
+  fn about_y(angle: f32) -> Quaternion
+  { Quaternion::new_from_axis(&Vec3f::new(0.0, 1.0, 0.0), angle) }
+
+  #[test]
+  fn axis_angle_round_trip()
+  {
+    for &(axis, angle) in [ (Vec3f::new(0.0, 1.0, 0.0), 1.0),
+                            (Vec3f::new(1.0, 2.0, 3.0), 2.5),
+                            (Vec3f::new(0.0, 0.0, -4.0), 0.3) ].iter()
+    {
+      let (out_axis, out_angle) = Quaternion::new_from_axis(&axis, angle).to_axis_angle();
+      assert!(close_vec(&out_axis, &Vec3f::new_normalized(&axis)));
+      assert!(near(out_angle, angle));
+    }
+
+    /* Radians, and the whole angle: a quarter turn about +Y
+     * takes +X to -Z. */
+    let quarter = about_y(f32::consts::PI / 2.0);
+    assert!(close_vec(&quarter.rotate_vec(&Vec3f::new(1.0, 0.0, 0.0)), &Vec3f::new(0.0, 0.0, -1.0)));
+
+    let (axis, angle) = Quaternion::identity().to_axis_angle();
+    assert!(close_vec(&axis, &Vec3f::new(1.0, 0.0, 0.0)));
+    assert!(near(angle, 0.0));
+  }
+
+  #[test]
+  fn inverse()
+  {
+    let q = Quaternion::new(1.0, 2.0, 3.0, 4.0);
+    assert!(close(&q.get_conjugate(), &Quaternion::new(-1.0, -2.0, -3.0, 4.0)));
+    assert!(close(&(q * q.inverse()), &Quaternion::identity()));
+    assert!(close(&(q.inverse() * q), &Quaternion::identity()));
+
+    /* For unit quaternions, it's the conjugate. */
+    let unit = Quaternion::new_from_axis(&Vec3f::new(1.0, 2.0, 3.0), 2.5);
+    assert!(close(&unit.inverse(), &unit.get_conjugate()));
+
+    assert!(close(&Quaternion::zero().inverse(), &Quaternion::zero()));
+  }
+
+  #[test]
+  fn interpolation_endpoints()
+  {
+    let from = Quaternion::identity();
+    let to = about_y(1.0);
+
+    assert!(close(&from.slerp(&to, 0.0), &from));
+    assert!(close(&from.slerp(&to, 1.0), &to));
+    assert!(close(&from.nlerp(&to, 0.0), &from));
+    assert!(close(&from.nlerp(&to, 1.0), &to));
+
+    /* Halfway is half the angle, for both. */
+    assert!(close(&from.slerp(&to, 0.5), &about_y(0.5)));
+    assert!(close(&from.nlerp(&to, 0.5), &about_y(0.5)));
+
+    /* The negated target is the same rotation; nlerp still takes
+     * the short way around. */
+    let mut flipped = to;
+    flipped.scale(-1.0);
+    assert!(close(&from.nlerp(&flipped, 0.5), &about_y(0.5)));
+  }
+
+  #[test]
+  fn squad()
+  {
+    let keys = [ about_y(0.0), about_y(1.0), about_y(2.0), about_y(3.0) ];
+    let a = Quaternion::squad_control(&keys[0], &keys[1], &keys[2]);
+    let b = Quaternion::squad_control(&keys[1], &keys[2], &keys[3]);
+
+    assert!(close(&keys[1].squad(&keys[2], &a, &b, 0.0), &keys[1]));
+    assert!(close(&keys[1].squad(&keys[2], &a, &b, 1.0), &keys[2]));
+
+    /* Evenly spaced about one axis, the curve is just slerp. */
+    assert!(close(&a, &keys[1]));
+    assert!(close(&keys[1].squad(&keys[2], &a, &b, 0.5), &about_y(1.5)));
+  }
+
+  #[test]
+  fn look_rotation_matches_camera()
+  {
+    let up = Vec3f::new(0.0, 1.0, 0.0);
+    let forward = Vec3f::new(0.0, 0.0, 1.0);
+
+    /* The camera's identity already looks down +Z. */
+    assert!(Quaternion::new_look_rotation(&forward, &up) == Quaternion::identity());
+
+    /* Whatever the direction, the camera's forward lands on it. */
+    for dir in [ Vec3f::new(1.0, 0.0, 0.0), Vec3f::new(0.0, 0.0, -1.0),
+                 Vec3f::new(1.0, -2.0, 3.0) ].iter()
+    {
+      let look = Quaternion::new_look_rotation(dir, &up);
+      assert!(look.rotate_vec(&forward) == Vec3f::new_normalized(dir));
+      assert!(look.rotate_vec(&up).y > 0.0);
+
+      /* Through a matrix and back; the sign may flip, the rotation can't. */
+      let back = Quaternion::new_from_mat(&look.to_mat());
+      assert!(close_vec(&back.rotate_vec(&forward), &look.rotate_vec(&forward)));
+      assert!(close_vec(&back.rotate_vec(&up), &look.rotate_vec(&up)));
+    }
+
+    /* Looking down +X is a quarter turn about +Y. */
+    let right = Quaternion::new_look_rotation(&Vec3f::new(1.0, 0.0, 0.0), &up);
+    assert!(close(&right, &about_y(f32::consts::PI / 2.0)));
+  }
+}
@@ -0,0 +1,228 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/random.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A small, seedable xoshiro256** generator
+      whose sequence depends only on its seed,
+      so the client and server can draw the same
+      numbers for prediction and demos.
+*/
+
+use std::f32;
+
+pub struct Random
+{
+  state: [u64, ..4],
+}
+
+impl Random
+{
+  /* The state is filled from the seed with splitmix64, as the
+   * xoshiro authors suggest, so that no seed gives a zero state. */
+  pub fn new(seed: u64) -> Random
+  {
+    let mut x = seed;
+    let mut state = [0u64, ..4];
+    for i in range(0u, 4u)
+    {
+      x += 0x9E3779B97F4A7C15;
+      let mut z = x;
+      z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9;
+      z = (z ^ (z >> 27)) * 0x94D049BB133111EB;
+      state[i] = z ^ (z >> 31);
+    }
+
+    Random { state: state }
+  }
+
+  /* Resumes from a state saved with get_state. */
+  pub fn new_from_state(state: [u64, ..4]) -> Random
+  { Random { state: state } }
+
+  pub fn get_state(&self) -> [u64, ..4]
+  { self.state }
+  pub fn set_state(&mut self, state: [u64, ..4])
+  { self.state = state; }
+
+  pub fn next_u64(&mut self) -> u64
+  {
+    let s = &mut self.state;
+    let result = rotate_left(s[1] * 5, 7) * 9;
+    let t = s[1] << 17;
+
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = rotate_left(s[3], 45);
+
+    result
+  }
+
+  /* The high bits are the strongest. */
+  pub fn next_u32(&mut self) -> u32
+  { (self.next_u64() >> 32) as u32 }
+
+  /* Uniform in [0, 1). */
+  pub fn next_f32(&mut self) -> f32
+  { ((self.next_u64() >> 40) as f32) / ((1 << 24) as f32) }
+
+  /* Uniform in [low, high); low when the range is empty. */
+  pub fn range_i32(&mut self, low: i32, high: i32) -> i32
+  {
+    if high <= low
+    { return low; }
+
+    let span = ((high as i64) - (low as i64)) as u64;
+    low + (((self.next_u32() as u64) * span) >> 32) as i32
+  }
+
+  /* Uniform in [low, high). */
+  pub fn range_f32(&mut self, low: f32, high: f32) -> f32
+  { low + ((high - low) * self.next_f32()) }
+
+  /* True with the given chance, in [0, 1]. */
+  pub fn chance(&mut self, chance: f32) -> bool
+  { self.next_f32() < chance }
+
+  /* Uniform over the surface of the unit sphere. */
+  pub fn unit_vec3(&mut self) -> super::Vec3f
+  {
+    let z = self.range_f32(-1.0, 1.0);
+    let angle = self.range_f32(0.0, f32::consts::PI * 2.0);
+    let r = (1.0 - (z * z)).sqrt();
+    super::Vec3f::new(r * angle.cos(), r * angle.sin(), z)
+  }
+
+  /* Uniform within the ball of the given radius. */
+  pub fn in_sphere(&mut self, radius: f32) -> super::Vec3f
+  {
+    /* Rejection sampling takes fewer than two tries on average. */
+    loop
+    {
+      let p = super::Vec3f::new(self.range_f32(-1.0, 1.0),
+                                self.range_f32(-1.0, 1.0),
+                                self.range_f32(-1.0, 1.0));
+      if p.dot(&p) <= 1.0
+      { return p * radius; }
+    }
+  }
+
+  /* A unit vector, uniform over the cap within half_angle radians
+   * of dir, such as for weapon spread. */
+  pub fn in_cone(&mut self, dir: &super::Vec3f, half_angle: f32) -> super::Vec3f
+  {
+    let axis = super::Vec3f::new_normalized(dir);
+    let z = self.range_f32(half_angle.cos(), 1.0);
+    let angle = self.range_f32(0.0, f32::consts::PI * 2.0);
+    let r = (1.0 - (z * z)).sqrt();
+
+    /* Any two directions perpendicular to the axis, and each other. */
+    let helper = if axis.x.abs() < 0.9
+    { super::Vec3f::new(1.0, 0.0, 0.0) }
+    else
+    { super::Vec3f::new(0.0, 1.0, 0.0) };
+    let side = super::Vec3f::new_normalized(&axis.cross(&helper));
+    let up = axis.cross(&side);
+
+    (side * (r * angle.cos())) + (up * (r * angle.sin())) + (axis * z)
+  }
+
+  /* An index into weights, picked in proportion to its weight.
+   * Negative weights count as zero; None if nothing has weight. */
+  pub fn weighted_choice(&mut self, weights: &[f32]) -> Option<uint>
+  {
+    let total = weights.iter().fold(0.0f32, |sum, &w| if w > 0.0 { sum + w } else { sum });
+    if !(total > 0.0)
+    { return None; }
+
+    let mut pick = self.next_f32() * total;
+    let mut last = 0;
+    for (i, &w) in weights.iter().enumerate()
+    {
+      if !(w > 0.0)
+      { continue; }
+      if pick < w
+      { return Some(i); }
+      pick -= w;
+      last = i;
+    }
+
+    /* Rounding can leave a sliver past the end. */
+    Some(last)
+  }
+}
+
+fn rotate_left(x: u64, k: u64) -> u64
+{ (x << k) | (x >> (64 - k)) }
+
+#[cfg(test)]
+mod test
+{
+  use super::Random;
+
+  /* Reference outputs of next_u64, for checking other ends against. */
+  static SEED_0: [u64, ..4] = [ 0x99ec5f36cb75f2b4, 0xbf6e1f784956452a,
+                                0x1a5f849d4933e6e0, 0x6aa594f1262d2d2c ];
+  static SEED_1: [u64, ..4] = [ 0xb3f2af6d0fc710c5, 0x853b559647364cea,
+                                0x92f89756082a4514, 0x642e1c7bc266a3a7 ];
+
+  #[test]
+  fn reference_vectors()
+  {
+    let mut rand = Random::new(0);
+    for &expected in SEED_0.iter()
+    { assert!(rand.next_u64() == expected); }
+
+    let mut rand = Random::new(1);
+    for &expected in SEED_1.iter()
+    { assert!(rand.next_u64() == expected); }
+  }
+
+  #[test]
+  fn state_round_trip()
+  {
+    let mut rand = Random::new(42);
+    for _ in range(0, 10)
+    { rand.next_u64(); }
+
+    let state = rand.get_state();
+    let expected = ~[ rand.next_u64(), rand.next_u64(), rand.next_u64() ];
+
+    let mut resumed = Random::new_from_state(state);
+    let mut reset = Random::new(7);
+    reset.set_state(state);
+    for &e in expected.iter()
+    {
+      assert!(resumed.next_u64() == e);
+      assert!(reset.next_u64() == e);
+    }
+  }
+
+  #[test]
+  fn same_seed_same_draws()
+  {
+    let weights = [ 0.5f32, 0.0, 2.0, -1.0, 1.5 ];
+    let mut a = Random::new(1234);
+    let mut b = Random::new(1234);
+    for _ in range(0, 256)
+    {
+      let num = a.range_i32(-1000, 1000);
+      assert!(num == b.range_i32(-1000, 1000));
+      assert!(num >= -1000 && num < 1000);
+
+      let pick = a.weighted_choice(weights);
+      assert!(pick == b.weighted_choice(weights));
+      /* Only the positive weights are ever picked. */
+      assert!(pick == Some(0) || pick == Some(2) || pick == Some(4));
+    }
+
+    assert!(a.range_i32(5, 5) == 5);
+    assert!(a.weighted_choice([ 0.0f32, -1.0 ]).is_none());
+  }
+}
@@ -0,0 +1,63 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/ray.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A ray, as origin + (dir * t) for t >= 0.
+      Hit distances are in multiples of dir, so
+      normalize it to get them in world units.
+*/
+
+use super::intersect;
+use super::intersect::Hit;
+
+pub struct Ray
+{
+  origin: super::Vec3f,
+  dir: super::Vec3f,
+}
+
+impl Ray
+{
+  pub fn new(origin: super::Vec3f, dir: super::Vec3f) -> Ray
+  { Ray { origin: origin, dir: dir } }
+
+  /* From one point toward another; t = 1 is the second point. */
+  pub fn new_between(from: &super::Vec3f, to: &super::Vec3f) -> Ray
+  { Ray { origin: *from, dir: *to - *from } }
+
+  pub fn at(&self, t: f32) -> super::Vec3f
+  { self.origin + (self.dir * t) }
+
+  pub fn intersect_plane(&self, plane: &super::Plane) -> Option<Hit>
+  { intersect::ray_plane(&self.origin, &self.dir, &plane.normal, plane.distance) }
+
+  pub fn intersect_aabb(&self, bb: &super::BB3) -> Option<Hit>
+  { intersect::ray_aabb(&self.origin, &self.dir, &bb.min, &bb.max) }
+
+  pub fn intersect_sphere(&self, sphere: &super::Sphere) -> Option<Hit>
+  { intersect::ray_sphere(&self.origin, &self.dir, &sphere.center, sphere.radius) }
+
+  pub fn intersect_triangle(&self, v0: &super::Vec3f, v1: &super::Vec3f, v2: &super::Vec3f) -> Option<Hit>
+  { intersect::ray_triangle(&self.origin, &self.dir, v0, v1, v2) }
+
+  /* Tested in the box's frame, so the normal is rotated back out. */
+  pub fn intersect_obb(&self, obb: &super::OBB) -> Option<Hit>
+  {
+    let local = Ray::new(obb.to_local(&self.origin), obb.to_local_dir(&self.dir));
+    let half = obb.half_extents;
+    match intersect::ray_aabb(&local.origin, &local.dir, &-half, &half)
+    {
+      Some(hit) => Some(Hit { distance: hit.distance, normal: obb.to_world_dir(&hit.normal) }),
+      None => None,
+    }
+  }
+
+  /* dir is transformed along with the origin, so hit distances
+   * still line up with points along the transformed ray. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> Ray
+  { Ray { origin: mat.transform_point(&self.origin), dir: mat.transform_dir(&self.dir) } }
+}
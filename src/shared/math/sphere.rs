@@ -0,0 +1,54 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/math/sphere.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A sphere, as a center and radius.
+*/
+
+use super::intersect;
+use super::intersect::Hit;
+
+pub struct Sphere
+{
+  center: super::Vec3f,
+  radius: f32,
+}
+
+impl Sphere
+{
+  pub fn new(center: super::Vec3f, radius: f32) -> Sphere
+  { Sphere { center: center, radius: radius } }
+
+  /* Inclusive of the surface. */
+  pub fn contains(&self, point: &super::Vec3f) -> bool
+  {
+    let offset = *point - self.center;
+    offset.dot(&offset) <= self.radius * self.radius
+  }
+
+  pub fn intersects_sphere(&self, other: &Sphere) -> bool
+  {
+    let offset = other.center - self.center;
+    let reach = self.radius + other.radius;
+    offset.dot(&offset) <= reach * reach
+  }
+
+  /* The normal pushes the sphere out of the box. */
+  pub fn intersects_aabb(&self, bb: &super::BB3) -> Option<Hit>
+  { intersect::sphere_aabb(&self.center, self.radius, &bb.min, &bb.max) }
+
+  pub fn bounds(&self) -> super::BB3
+  {
+    let r = super::Vec3f::new(self.radius, self.radius, self.radius);
+    super::BB3::new(self.center - r, self.center + r)
+  }
+
+  /* Non-uniform scale would make an ellipsoid; the radius grows by
+   * the largest scale, so the result still holds it. */
+  pub fn transform(&self, mat: &super::Mat4x4) -> Sphere
+  { Sphere { center: mat.transform_point(&self.center), radius: self.radius * mat.max_scale() } }
+}
@@ -11,15 +11,21 @@
 
 pub use self::vecf::Vec2f;
 pub use self::veci::Vec2i;
+pub use self::veci8::Vec2i8;
 pub use self::vecu8::Vec2u8;
 
 macro_rules! declare
 (
-  ($Type:ident, $Mod:ident, $Component:ty) =>
+  ($Type:ident, $Mod:ident, $Component:ty, $Larger:ident) =>
   (
     pub mod $Mod
     {
-      use std::{ ptr };
+      use std::{ cmp, ptr };
+      use log::Log;
+
+      #[macro_escape]
+      #[path = "../../log/macros.rs"]
+      mod macros;
 
       pub struct $Type
       {
@@ -50,6 +56,53 @@ macro_rules! declare
         { (((self.x * self.x) + 
             (self.y * self.y)) as f64).sqrt() as $Component }
 
+        /* Component-wise operations. */
+        pub fn min(&self, rhs: &$Type) -> $Type
+        { $Type{ x: cmp::min(self.x, rhs.x), y: cmp::min(self.y, rhs.y) } }
+
+        pub fn max(&self, rhs: &$Type) -> $Type
+        { $Type{ x: cmp::max(self.x, rhs.x), y: cmp::max(self.y, rhs.y) } }
+
+        pub fn clamp(&self, min: &$Type, max: &$Type) -> $Type
+        { self.max(min).min(max) }
+
+        pub fn abs(&self) -> $Type
+        { $Type{ x: (self.x as f64).abs() as $Component, y: (self.y as f64).abs() as $Component } }
+
+        pub fn floor(&self) -> $Type
+        { $Type{ x: (self.x as f64).floor() as $Component, y: (self.y as f64).floor() as $Component } }
+
+        pub fn ceil(&self) -> $Type
+        { $Type{ x: (self.x as f64).ceil() as $Component, y: (self.y as f64).ceil() as $Component } }
+
+        pub fn mul_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x * rhs.x, y: self.y * rhs.y } }
+
+        pub fn div_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x / rhs.x, y: self.y / rhs.y } }
+
+        /* Components picked by index, such as swizzle(1, 0) to reverse them. */
+        pub fn swizzle(&self, x: uint, y: uint) -> $Type
+        { $Type{ x: self[x], y: self[y] } }
+
+        pub fn extend(&self, z: $Component) -> super::super::$Larger
+        { super::super::$Larger::new(self.x, self.y, z) }
+
+        /* Casts between component types. The integer to_ casts saturate
+         * at the target's range; the try_to_ casts fail if any component
+         * doesn't survive. */
+        pub fn to_f(&self) -> super::Vec2f
+        { super::Vec2f::new(self.x as f32, self.y as f32) }
+
+        pub fn to_i(&self) -> super::Vec2i
+        { super::Vec2i::new(saturate!(self.x, i32), saturate!(self.y, i32)) }
+
+        pub fn to_i8(&self) -> super::Vec2i8
+        { super::Vec2i8::new(saturate!(self.x, i8), saturate!(self.y, i8)) }
+
+        pub fn to_u8(&self) -> super::Vec2u8
+        { super::Vec2u8::new(saturate!(self.x, u8), saturate!(self.y, u8)) }
+
         pub unsafe fn to_ptr(&self) -> *$Type
         { ptr::to_unsafe_ptr(self) } 
 
@@ -57,6 +110,11 @@ macro_rules! declare
         { format!("({}, {})", self.x, self.y) }
       }
 
+      try_to!($Type, try_to_f, to_f, Vec2f, x, y)
+      try_to!($Type, try_to_i, to_i, Vec2i, x, y)
+      try_to!($Type, try_to_i8, to_i8, Vec2i8, x, y)
+      try_to!($Type, try_to_u8, to_u8, Vec2u8, x, y)
+
       /***** Operator Overloads *****/
       impl Add<$Type, $Type> for $Type
       {
@@ -93,11 +151,24 @@ macro_rules! declare
                 y: ( -self.y ) }
         }
       }
+
+      impl Index<uint, $Component> for $Type
+      {
+        fn index(&self, rhs: &uint) -> $Component
+        {
+          match rhs
+          {
+            &0 => { self.x }
+            &1 => { self.y }
+            _ => { log_fail!("Invalid index to Vec2"); }
+          }
+        }
+      }
     }
   );
 )
 
-declare!(Vec2f, vecf, f32)
-declare!(Vec2i, veci, i32)
-declare!(Vec2u8, vecu8, u8)
-
+declare!(Vec2f, vecf, f32, Vec3f)
+declare!(Vec2i, veci, i32, Vec3i)
+declare!(Vec2i8, veci8, i8, Vec3i8)
+declare!(Vec2u8, vecu8, u8, Vec3u8)
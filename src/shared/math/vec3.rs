@@ -16,7 +16,7 @@ pub use self::vecu8::Vec3u8;
 
 macro_rules! declare
 (
-  ($Type:ident, $Mod:ident, $Component:ty) =>
+  ($Type:ident, $Mod:ident, $Component:ty, $Smaller:ident, $Larger:ident) =>
   (
     pub mod $Mod
     {
@@ -90,6 +90,85 @@ macro_rules! declare
           }
         }
 
+        /* Component-wise operations. */
+        pub fn min(&self, rhs: &$Type) -> $Type
+        {
+          $Type{ x: cmp::min(self.x, rhs.x),
+                 y: cmp::min(self.y, rhs.y),
+                 z: cmp::min(self.z, rhs.z) }
+        }
+
+        pub fn max(&self, rhs: &$Type) -> $Type
+        {
+          $Type{ x: cmp::max(self.x, rhs.x),
+                 y: cmp::max(self.y, rhs.y),
+                 z: cmp::max(self.z, rhs.z) }
+        }
+
+        pub fn clamp(&self, min: &$Type, max: &$Type) -> $Type
+        { self.max(min).min(max) }
+
+        pub fn abs(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).abs() as $Component,
+                 y: (self.y as f64).abs() as $Component,
+                 z: (self.z as f64).abs() as $Component }
+        }
+
+        pub fn floor(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).floor() as $Component,
+                 y: (self.y as f64).floor() as $Component,
+                 z: (self.z as f64).floor() as $Component }
+        }
+
+        pub fn ceil(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).ceil() as $Component,
+                 y: (self.y as f64).ceil() as $Component,
+                 z: (self.z as f64).ceil() as $Component }
+        }
+
+        pub fn mul_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x * rhs.x, y: self.y * rhs.y, z: self.z * rhs.z } }
+
+        pub fn div_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x / rhs.x, y: self.y / rhs.y, z: self.z / rhs.z } }
+
+        /* Components picked by index, such as swizzle(2, 1, 0) to reverse them. */
+        pub fn swizzle(&self, x: uint, y: uint, z: uint) -> $Type
+        { $Type{ x: self[x], y: self[y], z: self[z] } }
+
+        pub fn xy(&self) -> super::super::$Smaller
+        { super::super::$Smaller::new(self.x, self.y) }
+
+        pub fn xz(&self) -> super::super::$Smaller
+        { super::super::$Smaller::new(self.x, self.z) }
+
+        pub fn yz(&self) -> super::super::$Smaller
+        { super::super::$Smaller::new(self.y, self.z) }
+
+        pub fn extend(&self, w: $Component) -> super::super::$Larger
+        { super::super::$Larger::new(self.x, self.y, self.z, w) }
+
+        pub fn truncate(&self) -> super::super::$Smaller
+        { super::super::$Smaller::new(self.x, self.y) }
+
+        /* Casts between component types. The integer to_ casts saturate
+         * at the target's range; the try_to_ casts fail if any component
+         * doesn't survive. */
+        pub fn to_f(&self) -> super::Vec3f
+        { super::Vec3f::new(self.x as f32, self.y as f32, self.z as f32) }
+
+        pub fn to_i(&self) -> super::Vec3i
+        { super::Vec3i::new(saturate!(self.x, i32), saturate!(self.y, i32), saturate!(self.z, i32)) }
+
+        pub fn to_i8(&self) -> super::Vec3i8
+        { super::Vec3i8::new(saturate!(self.x, i8), saturate!(self.y, i8), saturate!(self.z, i8)) }
+
+        pub fn to_u8(&self) -> super::Vec3u8
+        { super::Vec3u8::new(saturate!(self.x, u8), saturate!(self.y, u8), saturate!(self.z, u8)) }
+
         pub unsafe fn to_ptr(&self) -> *$Type
         { ptr::to_unsafe_ptr(self) } 
 
@@ -97,6 +176,11 @@ macro_rules! declare
         { format!("({}, {}, {})", self.x, self.y, self.z) }
       }
 
+      try_to!($Type, try_to_f, to_f, Vec3f, x, y, z)
+      try_to!($Type, try_to_i, to_i, Vec3i, x, y, z)
+      try_to!($Type, try_to_i8, to_i8, Vec3i8, x, y, z)
+      try_to!($Type, try_to_u8, to_u8, Vec3u8, x, y, z)
+
       /***** Operator Overloads *****/
       impl Add<$Type, $Type> for $Type
       {
@@ -227,8 +311,7 @@ macro_rules! declare
   );
 )
 
-declare!(Vec3f, vecf, f32)
-declare!(Vec3i, veci, i32)
-declare!(Vec3i8, veci8, i8)
-declare!(Vec3u8, vecu8, u8)
-
+declare!(Vec3f, vecf, f32, Vec2f, Vec4f)
+declare!(Vec3i, veci, i32, Vec2i, Vec4i)
+declare!(Vec3i8, veci8, i8, Vec2i8, Vec4i8)
+declare!(Vec3u8, vecu8, u8, Vec2u8, Vec4u8)
@@ -10,15 +10,22 @@
 */
 
 pub use self::vecf::Vec4f;
+pub use self::veci::Vec4i;
+pub use self::veci8::Vec4i8;
 pub use self::vecu8::Vec4u8;
 
 macro_rules! declare
 (
-  ($Type:ident, $Mod:ident, $Component:ty) =>
+  ($Type:ident, $Mod:ident, $Component:ty, $Smaller:ident) =>
   (
     pub mod $Mod
     {
-      use std::{ ptr };
+      use std::{ cmp, ptr };
+      use log::Log;
+
+      #[macro_escape]
+      #[path = "../../log/macros.rs"]
+      mod macros;
 
       pub struct $Type
       {
@@ -55,6 +62,78 @@ macro_rules! declare
             (self.z * self.z) +
             (self.w * self.w)) as f64).sqrt() as $Component }
 
+        /* Component-wise operations. */
+        pub fn min(&self, rhs: &$Type) -> $Type
+        {
+          $Type{ x: cmp::min(self.x, rhs.x),
+                 y: cmp::min(self.y, rhs.y),
+                 z: cmp::min(self.z, rhs.z),
+                 w: cmp::min(self.w, rhs.w) }
+        }
+
+        pub fn max(&self, rhs: &$Type) -> $Type
+        {
+          $Type{ x: cmp::max(self.x, rhs.x),
+                 y: cmp::max(self.y, rhs.y),
+                 z: cmp::max(self.z, rhs.z),
+                 w: cmp::max(self.w, rhs.w) }
+        }
+
+        pub fn clamp(&self, min: &$Type, max: &$Type) -> $Type
+        { self.max(min).min(max) }
+
+        pub fn abs(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).abs() as $Component,
+                 y: (self.y as f64).abs() as $Component,
+                 z: (self.z as f64).abs() as $Component,
+                 w: (self.w as f64).abs() as $Component }
+        }
+
+        pub fn floor(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).floor() as $Component,
+                 y: (self.y as f64).floor() as $Component,
+                 z: (self.z as f64).floor() as $Component,
+                 w: (self.w as f64).floor() as $Component }
+        }
+
+        pub fn ceil(&self) -> $Type
+        {
+          $Type{ x: (self.x as f64).ceil() as $Component,
+                 y: (self.y as f64).ceil() as $Component,
+                 z: (self.z as f64).ceil() as $Component,
+                 w: (self.w as f64).ceil() as $Component }
+        }
+
+        pub fn mul_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x * rhs.x, y: self.y * rhs.y, z: self.z * rhs.z, w: self.w * rhs.w } }
+
+        pub fn div_elem(&self, rhs: &$Type) -> $Type
+        { $Type{ x: self.x / rhs.x, y: self.y / rhs.y, z: self.z / rhs.z, w: self.w / rhs.w } }
+
+        /* Components picked by index, such as swizzle(3, 2, 1, 0) to reverse them. */
+        pub fn swizzle(&self, x: uint, y: uint, z: uint, w: uint) -> $Type
+        { $Type{ x: self[x], y: self[y], z: self[z], w: self[w] } }
+
+        pub fn truncate(&self) -> super::super::$Smaller
+        { super::super::$Smaller::new(self.x, self.y, self.z) }
+
+        /* Casts between component types. The integer to_ casts saturate
+         * at the target's range; the try_to_ casts fail if any component
+         * doesn't survive. */
+        pub fn to_f(&self) -> super::Vec4f
+        { super::Vec4f::new(self.x as f32, self.y as f32, self.z as f32, self.w as f32) }
+
+        pub fn to_i(&self) -> super::Vec4i
+        { super::Vec4i::new(saturate!(self.x, i32), saturate!(self.y, i32), saturate!(self.z, i32), saturate!(self.w, i32)) }
+
+        pub fn to_i8(&self) -> super::Vec4i8
+        { super::Vec4i8::new(saturate!(self.x, i8), saturate!(self.y, i8), saturate!(self.z, i8), saturate!(self.w, i8)) }
+
+        pub fn to_u8(&self) -> super::Vec4u8
+        { super::Vec4u8::new(saturate!(self.x, u8), saturate!(self.y, u8), saturate!(self.z, u8), saturate!(self.w, u8)) }
+
         pub unsafe fn to_ptr(&self) -> *$Type
         { ptr::to_unsafe_ptr(self) } 
 
@@ -63,6 +142,11 @@ macro_rules! declare
           self.x, self.y, self.z, self.w) }
       }
 
+      try_to!($Type, try_to_f, to_f, Vec4f, x, y, z, w)
+      try_to!($Type, try_to_i, to_i, Vec4i, x, y, z, w)
+      try_to!($Type, try_to_i8, to_i8, Vec4i8, x, y, z, w)
+      try_to!($Type, try_to_u8, to_u8, Vec4u8, x, y, z, w)
+
       /***** Operator Overloads *****/
       impl Add<$Type, $Type> for $Type
       {
@@ -107,10 +191,26 @@ macro_rules! declare
                 w: ( -self.w ) }
         }
       }
+
+      impl Index<uint, $Component> for $Type
+      {
+        fn index(&self, rhs: &uint) -> $Component
+        {
+          match rhs
+          {
+            &0 => { self.x }
+            &1 => { self.y }
+            &2 => { self.z }
+            &3 => { self.w }
+            _ => { log_fail!("Invalid index to Vec4"); }
+          }
+        }
+      }
     }
   );
 )
 
-declare!(Vec4f, vecf, f32)
-declare!(Vec4u8, vecu8, u8)
-
+declare!(Vec4f, vecf, f32, Vec3f)
+declare!(Vec4i, veci, i32, Vec3i)
+declare!(Vec4i8, veci8, i8, Vec3i8)
+declare!(Vec4u8, vecu8, u8, Vec3u8)
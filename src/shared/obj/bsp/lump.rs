@@ -91,6 +91,18 @@ pub struct Plane
   /* Distance the plane is from the origin, along the normal. */
   distance: f32
 }
+impl Plane
+{
+  /* The plane in the map's space, which is how read_verts leaves
+   * the vertices: Y up, scaled down by 32, and then moved by the
+   * center. The math plane's distance runs the other way. */
+  pub fn to_math(&self, center: &math::Vec3f) -> math::Plane
+  {
+    let raw = self.normal;
+    let normal = math::Vec3f::new(raw.x, raw.z, -raw.y);
+    math::Plane::new(normal, normal.dot(center) - (self.distance / 32.0))
+  }
+}
 
 #[packed]
 pub struct Node
@@ -279,3 +291,29 @@ pub struct Vis_Data
   buffer: ~[u8]
 }
 
+
+#[cfg(test)]
+mod test
+{
+  use super::Plane;
+  use math;
+
+  #[test]
+  fn plane_to_math()
+  {
+    /* The Q3 plane z = 64 is y = 2 once Y is up and scaled, and
+     * y = 1 once the map is moved by its center. */
+    let plane = Plane { normal: math::Vec3f::new(0.0, 0.0, 1.0), distance: 64.0 };
+    let center = math::Vec3f::new(3.0, 1.0, -2.0);
+    let converted = plane.to_math(&center);
+    assert!(converted.normal == math::Vec3f::new(0.0, 1.0, 0.0));
+    assert!(converted.distance_to(&math::Vec3f::new(5.0, 1.0, 3.0)).approx_eq(&0.0));
+    assert!(converted.distance_to(&math::Vec3f::new(0.0, 3.0, 0.0)).approx_eq(&2.0));
+
+    /* Q3's +Y becomes -Z. */
+    let plane = Plane { normal: math::Vec3f::new(0.0, 1.0, 0.0), distance: 32.0 };
+    let converted = plane.to_math(&math::Vec3f::zero());
+    assert!(converted.normal == math::Vec3f::new(0.0, 0.0, -1.0));
+    assert!(converted.distance_to(&math::Vec3f::new(0.0, 0.0, -1.0)).approx_eq(&0.0));
+  }
+}
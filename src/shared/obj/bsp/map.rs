@@ -9,13 +9,14 @@
       Loader and handler of BSP maps.
 */
 
-use std::{ vec, cmp, ptr, mem, cast };
+use std::{ vec, ptr, mem, cast };
 use std::rt::io;
 use std::rt::io::{ Reader, Seek };
 use std::rt::io::File;
 use math;
 use super::lump;
 use primitive::{ Triangle, Vertex_PC };
+use mesh::Mesh;
 use log::Log;
 
 #[macro_escape]
@@ -32,6 +33,9 @@ pub struct Map
   mesh_verts: ~[lump::Mesh_Vert], 
   position: math::Vec3f,
   bb: math::BB3,
+  /* What the vertices were moved by to center them; planes
+   * need it to end up in the same space. */
+  center: math::Vec3f,
   error: ~str,
 }
 
@@ -49,6 +53,7 @@ impl Map
       mesh_verts: ~[],
       position: math::Vec3f::zero(),
       bb: math::BB3::zero(),
+      center: math::Vec3f::zero(),
       error: ~"",
     };
 
@@ -82,6 +87,10 @@ impl Map
     Ok(map)
   }
 
+  /* Writes the triangle soup to an OBJ or PLY file, by extension. */
+  pub fn export_mesh(&self, file: &str) -> Result<(), ~str>
+  { Mesh::export(self.tris, file) }
+
   fn read_verts(&mut self, fio: &mut io::File) -> bool
   {
     fio.seek(self.header.lumps[lump::Vertex_Type as int].offset as i64, io::SeekSet);
@@ -92,7 +101,7 @@ impl Map
 
     let mut vert = lump::Vertex::new();
     let mut buff = vec::from_elem(mem::size_of::<lump::Vertex>(), 0u8);
-    for i in range(0, num_verts)
+    for _ in range(0, num_verts)
     {
       unsafe
       {
@@ -122,58 +131,20 @@ impl Map
       vert.position.y /= 32.0;
       vert.position.z /= 32.0;
 
-      /* Create bounding box based on first vert; this'll update as more come. */
-      match i
-      {
-        0 =>
-        { self.bb.top_left = vert.position; self.bb.bottom_right = vert.position; }
-        _ =>
-        {
-          /* Keep track of bounds. */
-          if vert.position.x < self.bb.top_left.x
-          { self.bb.top_left.x = vert.position.x; }
-          else if vert.position.x > self.bb.bottom_right.x
-          { self.bb.bottom_right.x = vert.position.x }
-
-          if vert.position.y > self.bb.top_left.y
-          { self.bb.top_left.y = vert.position.y; }
-          else if vert.position.y < self.bb.bottom_right.y
-          { self.bb.bottom_right.y = vert.position.y }
-
-          if vert.position.z > self.bb.top_left.z
-          { self.bb.top_left.z = vert.position.z; }
-          else if vert.position.z < self.bb.bottom_right.z
-          { self.bb.bottom_right.z = vert.position.z }
-        }
-      }
-
       self.verts.push(vert);
     }
 
     /* Calculate the mesh's bounding box. */
-    let mut min = math::Vec3f::new( self.verts[0].position.x,
-                              self.verts[0].position.y, 
-                              self.verts[0].position.z);
-    let mut max = math::Vec3f::new( self.verts[0].position.x,
-                              self.verts[0].position.y,
-                              self.verts[0].position.z);
+    self.bb = math::BB3::new(self.verts[0].position, self.verts[0].position);
     for v in self.verts.iter()
-    {
-      min.x = cmp::min(min.x, v.position.x);
-      min.y = cmp::min(min.y, v.position.y);
-      min.z = cmp::min(min.z, v.position.z);
-
-      max.x = cmp::max(max.x, v.position.x);
-      max.y = cmp::max(max.y, v.position.y);
-      max.z = cmp::max(max.z, v.position.z);
-    }
-    let center = math::Vec3f::new(max.x - ((max.x - min.x) / 2.0),
-                            max.y - ((max.y - min.y) / 2.0),
-                            max.z - ((max.z - min.z) / 2.0));
+    { self.bb.expand(&v.position); }
+    let center = self.bb.center();
+    self.center = center;
 
     /* Move the mesh by the center to the origin (easier to voxelize). */
     for v in self.verts.mut_iter()
     { v.position = v.position - center; }
+    self.bb = math::BB3::new(self.bb.min - center, self.bb.max - center);
 
     true
   }
@@ -248,19 +219,13 @@ impl Map
             self.tris.push(Triangle::new( 
                         Vertex_PC::new(
                             self.verts[face.start_vertex].position,
-                            math::Vec3f::new( self.verts[face.start_vertex].color.x as f32,
-                                        self.verts[face.start_vertex].color.y as f32,
-                                        self.verts[face.start_vertex].color.z as f32)),
+                            self.verts[face.start_vertex].color.truncate().to_f()),
                         Vertex_PC::new(
                             self.verts[face.start_vertex + i + 2].position,
-                            math::Vec3f::new( self.verts[face.start_vertex + i + 2].color.x as f32,
-                                        self.verts[face.start_vertex + i + 2].color.y as f32,
-                                        self.verts[face.start_vertex + i + 2].color.z as f32)),
+                            self.verts[face.start_vertex + i + 2].color.truncate().to_f()),
                         Vertex_PC::new(
                             self.verts[face.start_vertex + i + 1].position,
-                            math::Vec3f::new( self.verts[face.start_vertex + i + 1].color.x as f32,
-                                        self.verts[face.start_vertex + i + 1].color.y as f32,
-                                        self.verts[face.start_vertex + i + 1].color.z as f32))));
+                            self.verts[face.start_vertex + i + 1].color.truncate().to_f())));
           }
         }
         /* Something else. */
@@ -0,0 +1,103 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/bytes.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Little-endian writing and reading of the
+      primitives our binary formats are built
+      from, such as .vox files and voxel patches.
+*/
+
+use std::{ str, cast };
+
+pub fn push_i32(buf: &mut ~[u8], val: i32)
+{
+  buf.push((val & 0xFF) as u8);
+  buf.push(((val >> 8) & 0xFF) as u8);
+  buf.push(((val >> 16) & 0xFF) as u8);
+  buf.push(((val >> 24) & 0xFF) as u8);
+}
+
+pub fn push_f32(buf: &mut ~[u8], val: f32)
+{ push_i32(buf, unsafe { cast::transmute::<f32, i32>(val) }); }
+
+/* Length-prefixed, without a terminator. */
+pub fn push_string(buf: &mut ~[u8], val: &str)
+{
+  push_i32(buf, val.len() as i32);
+  buf.push_all(val.as_bytes());
+}
+
+/* Reading over a loaded file. Each read is None, and leaves the
+ * position alone, if there isn't enough data left. */
+pub struct Buffer
+{
+  data: ~[u8],
+  pos: uint,
+}
+
+impl Buffer
+{
+  pub fn new(data: ~[u8]) -> Buffer
+  { Buffer { data: data, pos: 0 } }
+
+  pub fn remaining(&self) -> uint
+  { if self.pos < self.data.len() { self.data.len() - self.pos } else { 0 } }
+
+  pub fn read_i32(&mut self) -> Option<i32>
+  {
+    if self.remaining() < 4
+    { return None; }
+
+    let d = self.data.slice(self.pos, self.pos + 4);
+    self.pos += 4;
+    Some((d[0] as i32) | (d[1] as i32 << 8) | (d[2] as i32 << 16) | (d[3] as i32 << 24))
+  }
+
+  pub fn read_f32(&mut self) -> Option<f32>
+  {
+    match self.read_i32()
+    {
+      Some(bits) => Some(unsafe { cast::transmute::<i32, f32>(bits) }),
+      None => None,
+    }
+  }
+
+  /* Four bytes, such as a chunk ID or magic. */
+  pub fn read_id(&mut self) -> Option<~str>
+  {
+    if self.remaining() < 4
+    { return None; }
+
+    let id = str::from_utf8(self.data.slice(self.pos, self.pos + 4));
+    self.pos += 4;
+    Some(id)
+  }
+
+  pub fn read_string(&mut self) -> Option<~str>
+  {
+    let start = self.pos;
+    let len = match self.read_i32()
+    {
+      Some(len) if len >= 0 && (len as uint) <= self.remaining() => len as uint,
+      _ => { self.pos = start; return None; }
+    };
+
+    let val = str::from_utf8(self.data.slice(self.pos, self.pos + len));
+    self.pos += len;
+    Some(val)
+  }
+
+  pub fn read_bytes(&mut self, count: uint) -> Option<~[u8]>
+  {
+    if self.remaining() < count
+    { return None; }
+
+    let bytes = self.data.slice(self.pos, self.pos + count).to_owned();
+    self.pos += count;
+    Some(bytes)
+  }
+}
@@ -0,0 +1,92 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/mesh/export.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Writers of triangle soups to OBJ and
+      (ASCII) PLY files, with vert colors.
+      Both are text, so the output can be
+      diffed and inspected outside the game.
+*/
+
+use std::ascii::*;
+use std::rt::io::Writer;
+use std::rt::io::File;
+use primitive::Triangle;
+use super::Mesh;
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+impl Mesh
+{
+  /* Picks the writer based on the file's extension. Vert colors
+   * are expected to be in [0, 255], like the loaders produce. */
+  pub fn export(tris: &[Triangle], file: &str) -> Result<(), ~str>
+  {
+    let lower = file.to_ascii_lower();
+    let out = if lower.ends_with(".obj")
+    { to_obj(tris) }
+    else if lower.ends_with(".ply")
+    { to_ply(tris) }
+    else
+    { return Err(format!("Unsupported mesh format: {}", file)); };
+
+    let fio = File::create(&Path::new(file));
+    if fio.is_none()
+    { return Err(format!("Failed to write file: {}", file)); }
+    fio.unwrap().write(out.as_bytes());
+
+    log_info!("Exported {} triangles to {}", tris.len(), file);
+    Ok(())
+  }
+}
+
+/* Uses the same "v x y z r g b" extension the loader reads. */
+fn to_obj(tris: &[Triangle]) -> ~str
+{
+  let mut out = ~"# " + tris.len().to_str() + " triangles\n";
+  for tri in tris.iter()
+  {
+    for vert in tri.verts.iter()
+    {
+      out.push_str(format!("v {} {} {} {} {} {}\n",
+                           vert.position.x, vert.position.y, vert.position.z,
+                           vert.color.x / 255.0, vert.color.y / 255.0, vert.color.z / 255.0));
+    }
+  }
+  for i in range(0, tris.len())
+  { out.push_str(format!("f {} {} {}\n", (i * 3) + 1, (i * 3) + 2, (i * 3) + 3)); }
+  out
+}
+
+fn to_ply(tris: &[Triangle]) -> ~str
+{
+  let mut out = ~"ply\nformat ascii 1.0\n";
+  out.push_str(format!("element vertex {}\n", tris.len() * 3));
+  out.push_str("property float x\nproperty float y\nproperty float z\n");
+  out.push_str("property uchar red\nproperty uchar green\nproperty uchar blue\n");
+  out.push_str(format!("element face {}\n", tris.len()));
+  out.push_str("property list uchar int vertex_indices\nend_header\n");
+
+  for tri in tris.iter()
+  {
+    for vert in tri.verts.iter()
+    {
+      out.push_str(format!("{} {} {} {} {} {}\n",
+                           vert.position.x, vert.position.y, vert.position.z,
+                           to_byte(vert.color.x), to_byte(vert.color.y), to_byte(vert.color.z)));
+    }
+  }
+  for i in range(0, tris.len())
+  { out.push_str(format!("3 {} {} {}\n", i * 3, (i * 3) + 1, (i * 3) + 2)); }
+  out
+}
+
+fn to_byte(c: f32) -> u8
+{ c.round().clamp(&0.0, &255.0) as u8 }
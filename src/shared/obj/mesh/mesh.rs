@@ -0,0 +1,62 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/mesh/mesh.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A triangle soup loaded from an arbitrary
+      mesh file, ready to be voxelized.
+*/
+
+use std::ascii::*;
+use primitive::Triangle;
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+pub struct Mesh
+{
+  /* Vert colors are in [0, 255], like BSP maps. */
+  tris: ~[Triangle],
+  error: ~str,
+}
+
+impl Mesh
+{
+  /* Picks the loader based on the file's extension. */
+  pub fn new(file: &str) -> Result<Mesh, ~str>
+  {
+    let mut mesh = Mesh
+    {
+      tris: ~[],
+      error: ~"",
+    };
+
+    let lower = file.to_ascii_lower();
+    let loaded = if lower.ends_with(".obj")
+    { mesh.load_obj(file) }
+    else if lower.ends_with(".ply")
+    { mesh.load_ply(file) }
+    else
+    { mesh.error = format!("Unsupported mesh format: {}", file); false };
+
+    if !loaded
+    { return Err(mesh.error); }
+    if mesh.tris.len() == 0
+    { return Err(format!("No triangles in {}", file)); }
+
+    log_debug!("Loaded {} triangles from {}", mesh.tris.len(), file);
+    Ok(mesh)
+  }
+
+  pub fn is_supported(file: &str) -> bool
+  {
+    let lower = file.to_ascii_lower();
+    lower.ends_with(".obj") || lower.ends_with(".ply")
+  }
+}
+
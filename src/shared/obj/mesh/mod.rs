@@ -0,0 +1,18 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/mesh/mod.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      An aggregator of arbitrary mesh items.
+*/
+
+pub use self::mesh::Mesh;
+
+pub mod mesh;
+pub mod wavefront;
+pub mod ply;
+pub mod export;
+
@@ -0,0 +1,338 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/mesh/ply.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Loader of Stanford PLY meshes, in
+      ASCII or binary (either endianness).
+      Vert colors come from the red, green,
+      and blue vertex properties.
+*/
+
+use std::{ str, cast };
+use std::rt::io::Reader;
+use std::rt::io::File;
+use math;
+use primitive::{ Triangle, Vertex_PC };
+use super::Mesh;
+
+enum Format
+{
+  Ascii_Format,
+  Little_Endian_Format,
+  Big_Endian_Format,
+}
+
+enum Kind
+{
+  Int8_Kind,
+  UInt8_Kind,
+  Int16_Kind,
+  UInt16_Kind,
+  Int32_Kind,
+  UInt32_Kind,
+  Float32_Kind,
+  Float64_Kind,
+}
+
+struct Property
+{
+  name: ~str,
+  kind: Kind,
+  /* Set for list properties; the kind of the list's length. */
+  count_kind: Option<Kind>,
+}
+
+struct Element
+{
+  name: ~str,
+  count: uint,
+  properties: ~[Property],
+}
+
+impl Mesh
+{
+  pub fn load_ply(&mut self, file: &str) -> bool
+  {
+    let fio = File::open(&Path::new(file));
+    if fio.is_none()
+    { self.error = format!("Failed to read file: {}", file); return false; }
+    let data = fio.unwrap().read_to_end();
+
+    /* The header is always text and ends with its own line. */
+    let mut header_end = 0;
+    let marker = "end_header".as_bytes();
+    for i in range(0, data.len())
+    {
+      if data.len() - i >= marker.len() && data.slice(i, i + marker.len()) == marker
+      {
+        header_end = i + marker.len();
+        while header_end < data.len() && data[header_end] != '\n' as u8
+        { header_end += 1; }
+        header_end += 1;
+        break;
+      }
+    }
+    if header_end == 0 || header_end > data.len()
+    { self.error = ~"Missing PLY header"; return false; }
+    let header = str::from_utf8(data.slice(0, header_end));
+
+    let mut format = Ascii_Format;
+    let mut elements: ~[Element] = ~[];
+    for (line_num, line) in header.any_line_iter().enumerate()
+    {
+      let words: ~[&str] = line.word_iter().collect();
+      if line_num == 0
+      {
+        if words.len() != 1 || words[0] != "ply"
+        { self.error = ~"Invalid PLY magic"; return false; }
+        continue;
+      }
+      if words.len() == 0
+      { continue; }
+
+      match words[0]
+      {
+        "format" if words.len() >= 2 =>
+        {
+          format = match words[1]
+          {
+            "ascii" => Ascii_Format,
+            "binary_little_endian" => Little_Endian_Format,
+            "binary_big_endian" => Big_Endian_Format,
+            other => { self.error = format!("Unknown PLY format {}", other); return false; }
+          };
+        }
+        "element" if words.len() >= 3 =>
+        {
+          elements.push(Element
+          {
+            name: words[1].to_owned(),
+            count: from_str::<uint>(words[2]).unwrap_or(0),
+            properties: ~[],
+          });
+        }
+        "property" if elements.len() > 0 =>
+        {
+          let property = if words.len() >= 5 && words[1] == "list"
+          {
+            match (parse_kind(words[2]), parse_kind(words[3]))
+            {
+              (Some(count), Some(kind)) =>
+              { Property { name: words[4].to_owned(), kind: kind, count_kind: Some(count) } }
+              _ => { self.error = format!("Invalid PLY list property: {}", line); return false; }
+            }
+          }
+          else if words.len() >= 3
+          {
+            match parse_kind(words[1])
+            {
+              Some(kind) => { Property { name: words[2].to_owned(), kind: kind, count_kind: None } }
+              None => { self.error = format!("Invalid PLY property: {}", line); return false; }
+            }
+          }
+          else
+          { self.error = format!("Invalid PLY property: {}", line); return false; };
+
+          elements[elements.len() - 1].properties.push(property);
+        }
+        _ => { } /* Comments, obj_info, and end_header. */
+      }
+    }
+
+    let mut body = Body
+    {
+      format: format,
+      data: data.slice_from(header_end).to_owned(),
+      pos: 0,
+      tokens: ~[],
+    };
+    match format
+    {
+      Ascii_Format =>
+      {
+        let text = str::from_utf8(body.data);
+        body.tokens = text.word_iter().map(|w| w.to_owned()).collect();
+      }
+      _ => { }
+    }
+
+    let mut positions: ~[math::Vec3f] = ~[];
+    let mut colors: ~[math::Vec3f] = ~[];
+    for element in elements.iter()
+    {
+      for _ in range(0, element.count)
+      {
+        let mut pos = math::Vec3f::zero();
+        let mut color = math::Vec3f::new(255.0, 255.0, 255.0);
+        let mut face = ~[];
+
+        for property in element.properties.iter()
+        {
+          match property.count_kind
+          {
+            Some(count_kind) =>
+            {
+              let count = match body.read(count_kind)
+              {
+                Some(c) => c as uint,
+                None => { self.error = ~"Truncated PLY body"; return false; }
+              };
+              let is_face = match property.name.as_slice()
+              {
+                "vertex_indices" | "vertex_index" => true,
+                _ => false, /* Texture coords and such. */
+              };
+              for _ in range(0, count)
+              {
+                match body.read(property.kind)
+                {
+                  Some(v) => { if is_face { face.push(v as uint); } }
+                  None => { self.error = ~"Truncated PLY body"; return false; }
+                }
+              }
+            }
+            None =>
+            {
+              let val = match body.read(property.kind)
+              {
+                Some(v) => v as f32,
+                None => { self.error = ~"Truncated PLY body"; return false; }
+              };
+
+              /* Float colors are normalized; integer ones are bytes. */
+              let scale = match property.kind
+              {
+                Float32_Kind | Float64_Kind => 255.0,
+                _ => 1.0,
+              };
+              match property.name.as_slice()
+              {
+                "x" => { pos.x = val; }
+                "y" => { pos.y = val; }
+                "z" => { pos.z = val; }
+                "red" | "r" | "diffuse_red" => { color.x = val * scale; }
+                "green" | "g" | "diffuse_green" => { color.y = val * scale; }
+                "blue" | "b" | "diffuse_blue" => { color.z = val * scale; }
+                _ => { }
+              }
+            }
+          }
+        }
+
+        match element.name.as_slice()
+        {
+          "vertex" =>
+          {
+            positions.push(pos);
+            colors.push(color);
+          }
+          "face" =>
+          {
+            if face.len() < 3
+            { continue; }
+            for &index in face.iter()
+            {
+              if index >= positions.len()
+              { self.error = format!("PLY face index {} out of range", index); return false; }
+            }
+
+            /* Fan out any polygons into triangles. */
+            for i in range(1, face.len() - 1)
+            {
+              self.tris.push(Triangle::new(Vertex_PC::new(positions[face[0]], colors[face[0]]),
+                                           Vertex_PC::new(positions[face[i]], colors[face[i]]),
+                                           Vertex_PC::new(positions[face[i + 1]], colors[face[i + 1]])));
+            }
+          }
+          _ => { }
+        }
+      }
+    }
+
+    true
+  }
+}
+
+fn parse_kind(name: &str) -> Option<Kind>
+{
+  match name
+  {
+    "char" | "int8" => Some(Int8_Kind),
+    "uchar" | "uint8" => Some(UInt8_Kind),
+    "short" | "int16" => Some(Int16_Kind),
+    "ushort" | "uint16" => Some(UInt16_Kind),
+    "int" | "int32" => Some(Int32_Kind),
+    "uint" | "uint32" => Some(UInt32_Kind),
+    "float" | "float32" => Some(Float32_Kind),
+    "double" | "float64" => Some(Float64_Kind),
+    _ => None,
+  }
+}
+
+/* Everything after the header. */
+struct Body
+{
+  format: Format,
+  data: ~[u8],
+  pos: uint,
+  /* Only used for ASCII files. */
+  tokens: ~[~str],
+}
+
+impl Body
+{
+  fn read(&mut self, kind: Kind) -> Option<f64>
+  {
+    match self.format
+    {
+      Ascii_Format =>
+      {
+        if self.pos >= self.tokens.len()
+        { return None; }
+        self.pos += 1;
+        from_str::<f64>(self.tokens[self.pos - 1])
+      }
+      _ =>
+      {
+        let size = match kind
+        {
+          Int8_Kind | UInt8_Kind => 1,
+          Int16_Kind | UInt16_Kind => 2,
+          Int32_Kind | UInt32_Kind | Float32_Kind => 4,
+          Float64_Kind => 8,
+        };
+        if self.pos + size > self.data.len()
+        { return None; }
+
+        /* Assemble the raw bits in the file's byte order. */
+        let mut bits = 0u64;
+        for i in range(0, size)
+        {
+          let byte = match self.format
+          {
+            Big_Endian_Format => self.data[self.pos + i],
+            _ => self.data[self.pos + size - 1 - i],
+          };
+          bits = (bits << 8) | (byte as u64);
+        }
+        self.pos += size;
+
+        Some(match kind
+        {
+          Int8_Kind => (bits as u8 as i8) as f64,
+          UInt8_Kind => (bits as u8) as f64,
+          Int16_Kind => (bits as u16 as i16) as f64,
+          UInt16_Kind => (bits as u16) as f64,
+          Int32_Kind => (bits as u32 as i32) as f64,
+          UInt32_Kind => (bits as u32) as f64,
+          Float32_Kind => unsafe { cast::transmute::<u32, f32>(bits as u32) } as f64,
+          Float64_Kind => unsafe { cast::transmute::<u64, f64>(bits) },
+        })
+      }
+    }
+  }
+}
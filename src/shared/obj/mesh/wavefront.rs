@@ -0,0 +1,144 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/mesh/wavefront.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Loader of Wavefront OBJ meshes. Vert
+      colors are taken from the (unofficial)
+      "v x y z r g b" extension, then from the
+      face's .mtl diffuse color.
+*/
+
+use std::str;
+use std::hashmap::HashMap;
+use std::rt::io::Reader;
+use std::rt::io::File;
+use math;
+use primitive::{ Triangle, Vertex_PC };
+use super::Mesh;
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+impl Mesh
+{
+  pub fn load_obj(&mut self, file: &str) -> bool
+  {
+    let fio = File::open(&Path::new(file));
+    if fio.is_none()
+    { self.error = format!("Failed to read file: {}", file); return false; }
+    let contents = str::from_utf8(fio.unwrap().read_to_end());
+    let dir = str::from_utf8(Path::new(file).dirname());
+
+    let white = math::Vec3f::new(255.0, 255.0, 255.0);
+    let mut positions: ~[math::Vec3f] = ~[];
+    let mut colors: ~[Option<math::Vec3f>] = ~[];
+    let mut materials: HashMap<~str, math::Vec3f> = HashMap::new();
+    let mut material: Option<math::Vec3f> = None;
+
+    for (line_num, line) in contents.any_line_iter().enumerate()
+    {
+      let words: ~[&str] = line.word_iter().collect();
+      if words.len() == 0 || words[0].starts_with("#")
+      { continue; }
+
+      match words[0]
+      {
+        "v" =>
+        {
+          let nums: ~[f32] = words.slice_from(1).iter().filter_map(|w| from_str::<f32>(*w)).collect();
+          if nums.len() < 3
+          { self.error = format!("Invalid vertex on line {}", line_num + 1); return false; }
+
+          positions.push(math::Vec3f::new(nums[0], nums[1], nums[2]));
+          colors.push(if nums.len() >= 6
+          { Some(math::Vec3f::new(nums[3], nums[4], nums[5]) * 255.0) }
+          else
+          { None });
+        }
+        "f" =>
+        {
+          /* Only the position index of each v/vt/vn is wanted. */
+          let mut indices = ~[];
+          for word in words.slice_from(1).iter()
+          {
+            let index = match word.split_iter('/').next().and_then(|i| from_str::<i32>(i))
+            {
+              Some(i) if i > 0 => (i - 1) as uint,
+              Some(i) if i < 0 && (-i as uint) <= positions.len() => positions.len() - (-i as uint),
+              _ => { self.error = format!("Invalid face on line {}", line_num + 1); return false; }
+            };
+            if index >= positions.len()
+            { self.error = format!("Face index out of range on line {}", line_num + 1); return false; }
+            indices.push(index);
+          }
+          if indices.len() < 3
+          { self.error = format!("Degenerate face on line {}", line_num + 1); return false; }
+
+          let vert = |i: uint|
+          {
+            let color = match colors[i]
+            {
+              Some(c) => c,
+              None => material.unwrap_or(white),
+            };
+            Vertex_PC::new(positions[i], color)
+          };
+
+          /* Fan out any polygons into triangles. */
+          for i in range(1, indices.len() - 1)
+          { self.tris.push(Triangle::new(vert(indices[0]), vert(indices[i]), vert(indices[i + 1]))); }
+        }
+        "mtllib" =>
+        {
+          for name in words.slice_from(1).iter()
+          { load_mtl(format!("{}/{}", dir, *name), &mut materials); }
+        }
+        "usemtl" =>
+        {
+          material = if words.len() > 1
+          { materials.find(&words[1].to_owned()).map(|c| *c) }
+          else
+          { None };
+        }
+        _ => { } /* Normals, texture coords, groups, etc. don't matter. */
+      }
+    }
+
+    true
+  }
+}
+
+/* Reads the diffuse color of every material in the file. */
+fn load_mtl(file: &str, materials: &mut HashMap<~str, math::Vec3f>)
+{
+  let fio = File::open(&Path::new(file));
+  if fio.is_none()
+  { log_error!("Failed to read material file {}", file); return; }
+  let contents = str::from_utf8(fio.unwrap().read_to_end());
+
+  let mut name = ~"";
+  for line in contents.any_line_iter()
+  {
+    let words: ~[&str] = line.word_iter().collect();
+    if words.len() < 2
+    { continue; }
+
+    match words[0]
+    {
+      "newmtl" => { name = words[1].to_owned(); }
+      "Kd" if words.len() >= 4 =>
+      {
+        let nums: ~[f32] = words.slice_from(1).iter().filter_map(|w| from_str::<f32>(*w)).collect();
+        if nums.len() >= 3
+        { materials.insert(name.clone(), math::Vec3f::new(nums[0], nums[1], nums[2]) * 255.0); }
+      }
+      _ => { }
+    }
+  }
+}
@@ -18,14 +18,18 @@
 #[feature(managed_boxes)];
 
 extern mod extra;
+extern mod stb_image;
 
 extern mod log;
 extern mod math;
 
 pub use BSP_Map = self::bsp::map::Map;
 pub use Voxel_Map = self::voxel::map::Map;
+pub use Mesh = self::mesh::Mesh;
 
 pub mod bsp;
+pub mod bytes;
+pub mod mesh;
 pub mod primitive;
 pub mod voxel;
 
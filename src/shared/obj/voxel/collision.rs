@@ -0,0 +1,324 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/collision.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Swept AABB collision against the voxel
+      grid, for walking things around the
+      destructible world.
+*/
+
+use std::cmp;
+use math;
+use super::{ Map, Visible };
+
+/* How far boxes are kept from voxel faces to avoid
+ * starting the next sweep already touching them. */
+static SKIN: f32 = 0.001;
+/* How far down we look for something to stand on. */
+static GROUND_PROBE: f32 = 0.01;
+
+pub struct Sweep
+{
+  /* The movement that can be made without entering any voxels. */
+  movement: math::Vec3f,
+  /* Normals of every voxel face that blocked the movement. */
+  normals: ~[math::Vec3f],
+  /* Whether the box is resting on top of a voxel afterward. */
+  grounded: bool,
+}
+
+impl Map
+{
+  /* Moves the box through the grid one axis at a time so that it
+   * slides along walls. When a horizontal move is blocked while on
+   * the ground, climbing up to step_height is also tried. Without
+   * states, nothing can be known to be clear, so that's an error
+   * rather than a free move. */
+  pub fn sweep_aabb(&self, bb: &math::BB3, movement: math::Vec3f,
+                    step_height: f32) -> Result<Sweep, ~str>
+  {
+    let states = match self.states
+    {
+      Some(ref states) => states.as_slice(),
+      None => { return Err(~"Voxel states are unavailable"); }
+    };
+
+    let start_min = [bb.min.x, bb.min.y, bb.min.z];
+    let start_max = [bb.max.x, bb.max.y, bb.max.z];
+    let wanted = [movement.x, movement.y, movement.z];
+
+    let (probe, _) = self.sweep_axis(states, start_min, start_max, 1, -GROUND_PROBE);
+    let was_grounded = probe > -GROUND_PROBE;
+
+    /* Plain sliding move. */
+    let mut min = start_min;
+    let mut max = start_max;
+    let mut normals = ~[];
+    let mut moved = self.slide(states, &mut min, &mut max, wanted, &mut normals);
+
+    let blocked_sideways = (moved[0] != wanted[0]) || (moved[2] != wanted[2]);
+    if blocked_sideways && was_grounded && step_height > 0.0
+    {
+      /* Try again from higher up, then settle back down. */
+      let mut step_min = start_min;
+      let mut step_max = start_max;
+      let mut step_normals = ~[];
+
+      let (up, _) = self.sweep_axis(states, step_min, step_max, 1, step_height);
+      step_min[1] += up;
+      step_max[1] += up;
+
+      /* Rising, such as from a jump, carries on through the step;
+       * falling is left to the settle. */
+      let mut step_moved = self.slide(states, &mut step_min, &mut step_max,
+                                      [wanted[0], cmp::max(wanted[1], 0.0), wanted[2]],
+                                      &mut step_normals);
+
+      let (down, _) = self.sweep_axis(states, step_min, step_max, 1,
+                                      -up + cmp::min(wanted[1], 0.0));
+      step_min[1] += down;
+      step_max[1] += down;
+      step_moved[1] += up + down;
+
+      /* Only take the step if it got us further along. */
+      let flat = (moved[0] * moved[0]) + (moved[2] * moved[2]);
+      let stepped = (step_moved[0] * step_moved[0]) + (step_moved[2] * step_moved[2]);
+      if stepped > flat
+      {
+        min = step_min;
+        max = step_max;
+        moved = step_moved;
+        normals = step_normals;
+      }
+    }
+
+    let (below, _) = self.sweep_axis(states, min, max, 1, -GROUND_PROBE);
+
+    Ok(Sweep
+    {
+      movement: math::Vec3f::new(moved[0], moved[1], moved[2]),
+      normals: normals,
+      grounded: below > -GROUND_PROBE,
+    })
+  }
+
+  /* Moves the box along Y, then X, then Z. Returns how far it got on each. */
+  fn slide(&self, states: &[u32], min: &mut [f32, ..3], max: &mut [f32, ..3],
+           movement: [f32, ..3], normals: &mut ~[math::Vec3f]) -> [f32, ..3]
+  {
+    let mut moved = [0.0f32, ..3];
+    for &axis in [1u, 0u, 2u].iter()
+    {
+      if movement[axis] == 0.0
+      { continue; }
+
+      let (allowed, blocked) = self.sweep_axis(states, *min, *max, axis, movement[axis]);
+      if blocked
+      {
+        let mut normal = [0.0f32, ..3];
+        normal[axis] = if movement[axis] > 0.0 { -1.0 } else { 1.0 };
+        normals.push(math::Vec3f::new(normal[0], normal[1], normal[2]));
+      }
+
+      min[axis] += allowed;
+      max[axis] += allowed;
+      moved[axis] = allowed;
+    }
+
+    moved
+  }
+
+  /* Determines how far the box can move along a single axis
+   * and whether a voxel got in the way. */
+  fn sweep_axis(&self, states: &[u32], min: [f32, ..3], max: [f32, ..3],
+                axis: uint, delta: f32) -> (f32, bool)
+  {
+    if delta == 0.0
+    { return (0.0, false); }
+
+    /* Grid space has one unit per voxel. */
+    let grid_min = self.world_to_grid(&math::Vec3f::new(min[0], min[1], min[2]));
+    let grid_max = self.world_to_grid(&math::Vec3f::new(max[0], max[1], max[2]));
+    let grid_delta = delta / self.voxel_size;
+    let epsilon = SKIN / self.voxel_size;
+
+    /* Cells covered by the box on the other two axes. */
+    let mut lo = [0i32, ..3];
+    let mut hi = [0i32, ..3];
+    for q in range(0u, 3u)
+    {
+      lo[q] = (grid_min[q] + epsilon).floor() as i32;
+      hi[q] = (grid_max[q] - epsilon).floor() as i32;
+    }
+
+    /* Walk the slabs of cells in the direction of movement. */
+    let (first, last, dir) = if delta > 0.0
+    {
+      ((grid_max[axis] - epsilon).floor() as i32 + 1,
+       (grid_max[axis] + grid_delta - epsilon).floor() as i32,
+       1)
+    }
+    else
+    {
+      ((grid_min[axis] + epsilon).floor() as i32 - 1,
+       (grid_min[axis] + grid_delta + epsilon).floor() as i32,
+       -1)
+    };
+
+    let mut slab = first;
+    while (dir > 0 && slab <= last) || (dir < 0 && slab >= last)
+    {
+      lo[axis] = slab;
+      hi[axis] = slab;
+      if self.any_solid(states, lo, hi)
+      {
+        let (cell_min, cell_max) = self.cell_bounds(&math::Vec3i::new(lo[0], lo[1], lo[2]));
+        return if dir > 0
+        { (cmp::max(cell_min[axis] - max[axis] - SKIN, 0.0), true) }
+        else
+        { (cmp::min(cell_max[axis] - min[axis] + SKIN, 0.0), true) };
+      }
+      slab += dir;
+    }
+
+    (delta, false)
+  }
+
+  /* Checks an inclusive range of cells for visible voxels.
+   * Anything outside of the grid is treated as empty space. */
+  fn any_solid(&self, states: &[u32], lo: [i32, ..3], hi: [i32, ..3]) -> bool
+  {
+    let dims = self.dimensions;
+    for z in range(cmp::max(lo[2], 0), cmp::min(hi[2], dims.z - 1) + 1)
+    {
+      for y in range(cmp::max(lo[1], 0), cmp::min(hi[1], dims.y - 1) + 1)
+      {
+        for x in range(cmp::max(lo[0], 0), cmp::min(hi[0], dims.x - 1) + 1)
+        {
+          if (states[self.cell_index(&math::Vec3i::new(x, y, z))] & Visible) != 0
+          { return true; }
+        }
+      }
+    }
+
+    false
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use super::Sweep;
+  use super::super::{ Map, Vertex };
+
+  /* The box is 0.6 across and 1.8 tall. */
+  static HALF: [f32, ..3] = [ 0.3, 0.9, 0.3 ];
+
+  /* An 8x8x8 grid, one unit per voxel, with a floor along y = 0 and
+   * columns of voxels from y = 1 up to (not including) height, over
+   * the x range [x_start, x_end) and all of z. */
+  fn new_grid(x_start: i32, x_end: i32, height: i32) -> @mut Map
+  {
+    let mut voxels = ~[];
+    for z in range(0, 8)
+    { for y in range(0, 8)
+      { for x in range(0, 8)
+        {
+          if y == 0 || (x >= x_start && x < x_end && y < height)
+          { voxels.push(Vertex::new_with_position(math::Vec3f::new(x as f32, y as f32, z as f32))); }
+        }
+      }
+    }
+    Map::new_with_voxels(math::Vec3i::new(8, 8, 8), 1.0, math::Vec3f::zero(), voxels).unwrap()
+  }
+
+  fn box_at(center: math::Vec3f) -> math::BB3
+  {
+    let half = math::Vec3f::new(HALF[0], HALF[1], HALF[2]);
+    math::BB3::new(center - half, center + half)
+  }
+
+  /* Standing on the floor, just off of its top at y = 1. */
+  fn sweep(map: &Map, movement: math::Vec3f, step_height: f32) -> Sweep
+  {
+    let bb = box_at(math::Vec3f::new(4.5, 1.0 + HALF[1] + 0.001, 4.5));
+    map.sweep_aabb(&bb, movement, step_height).unwrap()
+  }
+
+  fn near(a: f32, b: f32) -> bool
+  { (a - b).abs() < 0.0001 }
+
+  #[test]
+  fn lands_on_floor()
+  {
+    let map = new_grid(0, 0, 0);
+    let result = map.sweep_aabb(&box_at(math::Vec3f::new(4.5, 3.0, 4.5)),
+                                math::Vec3f::new(0.0, -5.0, 0.0), 0.0).unwrap();
+
+    /* The box's bottom, at 2.1, comes to rest just above 1. */
+    assert!(near(result.movement.y, -1.099));
+    assert!(result.grounded);
+    assert!(result.normals.len() == 1);
+    assert!(result.normals[0] == math::Vec3f::new(0.0, 1.0, 0.0));
+  }
+
+  #[test]
+  fn slides_along_wall()
+  {
+    /* A wall at x = 6; the box's side starts at 4.8. */
+    let map = new_grid(6, 7, 4);
+    let result = sweep(&*map, math::Vec3f::new(3.0, 0.0, 1.0), 0.0);
+    assert!(near(result.movement.x, 1.199));
+    assert!(near(result.movement.y, 0.0));
+    assert!(near(result.movement.z, 1.0));
+    assert!(result.grounded);
+    assert!(result.normals.len() == 1);
+    assert!(result.normals[0] == math::Vec3f::new(-1.0, 0.0, 0.0));
+  }
+
+  #[test]
+  fn steps_up()
+  {
+    /* A ledge one voxel high, from x = 6 onward. */
+    let map = new_grid(6, 8, 2);
+    let result = sweep(&*map, math::Vec3f::new(3.0, 0.0, 0.0), 1.1);
+    assert!(near(result.movement.x, 3.0));
+    assert!(near(result.movement.y, 1.0));
+    assert!(result.grounded);
+  }
+
+  #[test]
+  fn refuses_tall_step()
+  {
+    let map = new_grid(6, 8, 2);
+    let result = sweep(&*map, math::Vec3f::new(3.0, 0.0, 0.0), 0.5);
+    assert!(near(result.movement.x, 1.199));
+    assert!(near(result.movement.y, 0.0));
+  }
+
+  #[test]
+  fn jumps_onto_ledge()
+  {
+    /* Two voxels high: neither the jump nor the step clears it alone,
+     * but together they do, and the box lands on top. */
+    let map = new_grid(6, 8, 3);
+    let result = sweep(&*map, math::Vec3f::new(3.0, 1.5, 0.0), 1.1);
+    assert!(near(result.movement.x, 3.0));
+    assert!(near(result.movement.y, 2.0));
+    assert!(result.grounded);
+  }
+
+  #[test]
+  fn states_unavailable()
+  {
+    let map = new_grid(0, 0, 0);
+    map.states = None;
+    assert!(map.sweep_aabb(&box_at(math::Vec3f::new(4.5, 3.0, 4.5)),
+                           math::Vec3f::new(0.0, -1.0, 0.0), 0.0).is_err());
+  }
+}
@@ -0,0 +1,425 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/compact.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      A palette-indexed form of the voxel map's
+      voxels. Each voxel is its packed grid cell
+      and a palette index; five or six bytes
+      rather than the 24 of a Vertex. This is
+      what gets uploaded and sent around; the
+      map still keeps its full voxels, for
+      carving, patching, and ray casts.
+*/
+
+use std::{ vec, cmp };
+use std::hashmap::HashMap;
+use math;
+use super::{ Map, Vertex };
+
+pub enum Palette_Size
+{
+  /* Up to 256 colors, with byte indices. */
+  Small_Palette,
+  /* Up to 65536 colors, with short indices. */
+  Large_Palette,
+}
+
+pub enum Palette_Indices
+{
+  Small_Indices(~[u8]),
+  Large_Indices(~[u16]),
+}
+
+pub struct Compact
+{
+  /* Each voxel's cell, as its index into Map::states. */
+  cells: ~[u32],
+  /* RGBA, ready to be used as a texture buffer. */
+  palette: ~[[u8, ..4]],
+  indices: Palette_Indices,
+}
+
+impl Compact
+{
+  pub fn len(&self) -> uint
+  { self.cells.len() }
+
+  /* The palette index of voxel i. */
+  pub fn index(&self, i: uint) -> uint
+  {
+    match self.indices
+    {
+      Small_Indices(ref indices) => indices[i] as uint,
+      Large_Indices(ref indices) => indices[i] as uint,
+    }
+  }
+
+  /* The color of voxel i, in [0, 1]. */
+  pub fn color(&self, i: uint) -> math::Vec3f
+  {
+    let entry = self.palette[self.index(i)];
+    math::Vec3f::new(entry[0] as f32 / 255.0, entry[1] as f32 / 255.0, entry[2] as f32 / 255.0)
+  }
+}
+
+impl Map
+{
+  /* Voxel i of the compact form is voxel i of the map, so
+   * states can be used to index either. */
+  pub fn to_compact(&self, size: Palette_Size) -> Compact
+  {
+    let cells = do self.voxels.map |vox|
+    {
+      let cell = vox.position.to_i();
+      self.cell_index(&cell) as u32
+    };
+
+    let (palette, indices) = match size
+    {
+      Small_Palette =>
+      {
+        let (palette, indices) = quantize(self.voxels, 256, 5);
+        (palette, Small_Indices(indices.map(|i| *i as u8)))
+      }
+      Large_Palette =>
+      {
+        let (palette, indices) = quantize(self.voxels, 65536, 6);
+        (palette, Large_Indices(indices))
+      }
+    };
+
+    Compact
+    {
+      cells: cells,
+      palette: do palette.map |color|
+      {
+        [ (color.x.clamp(&0.0, &1.0) * 255.0).round() as u8,
+          (color.y.clamp(&0.0, &1.0) * 255.0).round() as u8,
+          (color.z.clamp(&0.0, &1.0) * 255.0).round() as u8,
+          255 ]
+      },
+      indices: indices,
+    }
+  }
+
+  /* Expands a compact form back into a full map. */
+  pub fn new_from_compact(dimensions: math::Vec3i, voxel_size: f32, origin: math::Vec3f,
+                          compact: &Compact) -> Result<@mut Map, ~str>
+  {
+    let count = (dimensions.x * dimensions.y * dimensions.z) as uint;
+    let mut voxels = vec::with_capacity(compact.len());
+    for i in range(0, compact.len())
+    {
+      if compact.cells[i] as uint >= count
+      { return Err(format!("Compact voxel {} is outside of the grid", i)); }
+      if compact.index(i) >= compact.palette.len()
+      { return Err(format!("Compact voxel {} has no palette entry", i)); }
+
+      let cell = super::map::grid_cell(&dimensions, compact.cells[i] as uint);
+      voxels.push(Vertex
+      {
+        position: cell.to_f(),
+        color: compact.color(i),
+      });
+    }
+
+    Map::new_with_voxels(dimensions, voxel_size, origin, voxels)
+  }
+}
+
+/* When every distinct 8-bit color fits, the palette is exactly those
+ * colors. Otherwise, this is popularity quantization: colors are binned
+ * with the given number of bits per channel, the most popular bins
+ * (averaged) make the palette, and every other bin maps to a near
+ * entry. Returns the palette and each voxel's zero-based index into it. */
+pub fn quantize(voxels: &[Vertex], max_colors: uint, bits: uint) -> (~[math::Vec3f], ~[u16])
+{
+  match exact_palette(voxels, max_colors)
+  {
+    Some(exact) => { return exact; }
+    None => { }
+  }
+
+  let bucket_count = 1u << (bits * 3);
+  let mut counts = vec::from_elem(bucket_count, 0u32);
+  let mut sums = vec::from_elem(bucket_count, math::Vec3f::zero());
+  let mut used = ~[];
+  for vox in voxels.iter()
+  {
+    let bucket = bucket_of(&vox.color, bits);
+    if counts[bucket] == 0
+    { used.push(bucket); }
+    counts[bucket] += 1;
+    sums[bucket] = sums[bucket] + vox.color;
+  }
+
+  /* Find the smallest count which still makes the cut; every
+   * bucket above it is in and the ties fill what's left. */
+  let mut threshold = 1u32;
+  if used.len() > max_colors
+  {
+    let mut low = 1u32;
+    let mut high = used.iter().fold(1u32, |m, &b| if counts[b] > m { counts[b] } else { m });
+    while low < high
+    {
+      let mid = low + ((high - low + 1) / 2);
+      let above = used.iter().count(|&b| counts[b] >= mid);
+      if above >= max_colors
+      { low = mid; }
+      else
+      { high = mid - 1; }
+    }
+    threshold = low;
+  }
+
+  let mut palette = ~[];
+  let mut nearest = vec::from_elem(bucket_count, -1i32);
+  for pass in range(0, 2)
+  {
+    for &bucket in used.iter()
+    {
+      if palette.len() >= max_colors
+      { break; }
+      let take = if pass == 0
+      { counts[bucket] > threshold }
+      else
+      { counts[bucket] == threshold };
+      if take && nearest[bucket] < 0
+      {
+        nearest[bucket] = palette.len() as i32;
+        palette.push(sums[bucket] * (1.0 / (counts[bucket] as f32)));
+      }
+    }
+  }
+
+  /* Map each remaining bucket to a near palette entry, caching as we go.
+   * Entries are sorted into a coarse grid so that only the cells around
+   * the color are searched, rather than the whole palette. */
+  let grid = Coarse_Grid::new(palette);
+  let indices = do voxels.map |vox|
+  {
+    let bucket = bucket_of(&vox.color, bits);
+    if nearest[bucket] < 0
+    { nearest[bucket] = grid.nearest(palette, &(sums[bucket] * (1.0 / (counts[bucket] as f32)))) as i32; }
+    nearest[bucket] as u16
+  };
+
+  (palette, indices)
+}
+
+/* The palette of every distinct color, at eight bits per channel,
+ * or None if there are more than max_colors of them. */
+fn exact_palette(voxels: &[Vertex], max_colors: uint) -> Option<(~[math::Vec3f], ~[u16])>
+{
+  let mut entries = HashMap::<u32, u16>::new();
+  let mut palette = ~[];
+  let mut indices = vec::with_capacity(voxels.len());
+  for vox in voxels.iter()
+  {
+    let key = bucket_of(&vox.color, 8) as u32;
+    let index = match entries.find(&key)
+    {
+      Some(index) => { Some(*index) }
+      None => { None }
+    };
+    match index
+    {
+      Some(index) => { indices.push(index); }
+      None =>
+      {
+        if palette.len() >= max_colors
+        { return None; }
+
+        let index = palette.len() as u16;
+        entries.insert(key, index);
+        palette.push(math::Vec3f::new(((key >> 16) & 0xFF) as f32 / 255.0,
+                                      ((key >> 8) & 0xFF) as f32 / 255.0,
+                                      (key & 0xFF) as f32 / 255.0));
+        indices.push(index);
+      }
+    }
+  }
+
+  Some((palette, indices))
+}
+
+fn bucket_of(color: &math::Vec3f, bits: uint) -> uint
+{
+  let levels = ((1u << bits) - 1) as f32;
+  let r = (color.x.clamp(&0.0, &1.0) * levels).round() as uint;
+  let g = (color.y.clamp(&0.0, &1.0) * levels).round() as uint;
+  let b = (color.z.clamp(&0.0, &1.0) * levels).round() as uint;
+  (r << (bits * 2)) | (g << bits) | b
+}
+
+/* Palette entries, by index, sorted into COARSE_LEVELS^3 cells. */
+static COARSE_LEVELS: i32 = 16;
+struct Coarse_Grid
+{
+  cells: ~[~[uint]],
+}
+
+impl Coarse_Grid
+{
+  fn new(palette: &[math::Vec3f]) -> Coarse_Grid
+  {
+    let count = (COARSE_LEVELS * COARSE_LEVELS * COARSE_LEVELS) as uint;
+    let mut cells = vec::from_fn(count, |_| ~[]);
+    for (i, entry) in palette.iter().enumerate()
+    {
+      let (x, y, z) = Coarse_Grid::cell_of(entry);
+      cells[Coarse_Grid::index(x, y, z)].push(i);
+    }
+    Coarse_Grid { cells: cells }
+  }
+
+  fn cell_of(color: &math::Vec3f) -> (i32, i32, i32)
+  {
+    let levels = COARSE_LEVELS as f32;
+    let max = COARSE_LEVELS - 1;
+    (((color.x * levels).floor() as i32).clamp(&0, &max),
+     ((color.y * levels).floor() as i32).clamp(&0, &max),
+     ((color.z * levels).floor() as i32).clamp(&0, &max))
+  }
+
+  fn index(x: i32, y: i32, z: i32) -> uint
+  { ((((z * COARSE_LEVELS) + y) * COARSE_LEVELS) + x) as uint }
+
+  /* Grows a cube of cells around the color until it holds an entry,
+   * then searches one cell further, since an entry in the next ring
+   * can still be closer. That's almost always the nearest entry, and
+   * otherwise one very near it. */
+  fn nearest(&self, palette: &[math::Vec3f], color: &math::Vec3f) -> uint
+  {
+    let (cx, cy, cz) = Coarse_Grid::cell_of(color);
+    let mut best = 0;
+    let mut best_dist = -1.0f32;
+    let mut last_radius = COARSE_LEVELS;
+    let mut radius = 0;
+    while radius <= last_radius
+    {
+      for z in range(cmp::max(cz - radius, 0), cmp::min(cz + radius, COARSE_LEVELS - 1) + 1)
+      { for y in range(cmp::max(cy - radius, 0), cmp::min(cy + radius, COARSE_LEVELS - 1) + 1)
+        { for x in range(cmp::max(cx - radius, 0), cmp::min(cx + radius, COARSE_LEVELS - 1) + 1)
+          {
+            /* The inner cells were searched on earlier rings. */
+            let ring = cmp::max((x - cx).abs(), cmp::max((y - cy).abs(), (z - cz).abs()));
+            if ring != radius
+            { continue; }
+
+            for &i in self.cells[Coarse_Grid::index(x, y, z)].iter()
+            {
+              let diff = palette[i] - *color;
+              let dist = diff.dot(&diff);
+              if best_dist < 0.0 || dist < best_dist
+              { best = i; best_dist = dist; }
+            }
+          }
+        }
+      }
+
+      if best_dist >= 0.0 && last_radius == COARSE_LEVELS
+      { last_radius = radius + 1; }
+      radius += 1;
+    }
+
+    best
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use super::{ quantize, Small_Palette, Large_Palette };
+  use super::super::{ Map, Vertex, Visible };
+
+  /* Colors which are exact at eight bits per channel. */
+  fn color(r: uint, g: uint, b: uint) -> math::Vec3f
+  { math::Vec3f::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0) }
+
+  fn voxel(c: math::Vec3f) -> Vertex
+  { Vertex::new(math::Vec3f::zero(), c) }
+
+  #[test]
+  fn exact_when_colors_fit()
+  {
+    let mut voxels = ~[];
+    for i in range(0u, 200u)
+    {
+      voxels.push(voxel(color(i, 255 - i, (i * 7) % 256)));
+      voxels.push(voxel(color(i, 255 - i, (i * 7) % 256)));
+    }
+
+    let (palette, indices) = quantize(voxels, 256, 5);
+    assert!(palette.len() == 200);
+    for (vox, &index) in voxels.iter().zip(indices.iter())
+    {
+      let entry = palette[index as uint];
+      assert!(entry.x == vox.color.x && entry.y == vox.color.y && entry.z == vox.color.z);
+    }
+  }
+
+  #[test]
+  fn palette_is_bounded()
+  {
+    /* 4096 distinct colors, all equally popular, then with one far
+     * more popular than the rest. */
+    let mut voxels = ~[];
+    for r in range(0u, 16u)
+    { for g in range(0u, 16u)
+      { for b in range(0u, 16u)
+        { voxels.push(voxel(color(r * 17, g * 17, b * 17))); }
+      }
+    }
+
+    for &max in [ 256u, 100u, 7u, 1u ].iter()
+    {
+      let (palette, indices) = quantize(voxels, max, 5);
+      assert!(palette.len() == max);
+      for &index in indices.iter()
+      { assert!((index as uint) < palette.len()); }
+    }
+
+    let popular = color(17, 34, 51);
+    for _ in range(0, 100)
+    { voxels.push(voxel(popular)); }
+    let (palette, indices) = quantize(voxels, 7, 5);
+    assert!(palette.len() == 7);
+    assert!(palette[indices[voxels.len() - 1] as uint] == popular);
+  }
+
+  #[test]
+  fn compact_round_trip()
+  {
+    let cells = [ (0, 0, 0), (3, 1, 2), (1, 2, 3), (3, 3, 3) ];
+    let mut voxels = ~[];
+    for (i, &(x, y, z)) in cells.iter().enumerate()
+    { voxels.push(Vertex::new(math::Vec3f::new(x as f32, y as f32, z as f32), color(i * 60, 255 - (i * 60), 128))); }
+    let dims = math::Vec3i::new(4, 4, 4);
+    let map = Map::new_with_voxels(dims, 0.25, math::Vec3f::new(1.0, 2.0, 3.0), voxels).unwrap();
+
+    for &size in [ Small_Palette, Large_Palette ].iter()
+    {
+      let compact = map.to_compact(size);
+      assert!(compact.len() == cells.len());
+      let copy = Map::new_from_compact(dims, map.voxel_size, map.origin, &compact).unwrap();
+
+      let (states, copy_states) = (map.states.get_ref(), copy.states.get_ref());
+      for i in range(0, states.len())
+      {
+        assert!((states[i] & Visible) == (copy_states[i] & Visible));
+        if (states[i] & Visible) != 0
+        {
+          let (a, b) = (map.voxels[states[i] & !Visible], copy.voxels[copy_states[i] & !Visible]);
+          assert!(a.position == b.position);
+          assert!(a.color == b.color);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,377 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/generator.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Procedural arenas, built directly as voxels
+      from a seed: noise terrain, walled rooms
+      joined by corridors, and pillars. The same
+      seed and parameters always produce the same
+      layout and spawn points.
+*/
+
+use std::{ vec, cmp };
+use math;
+use super::{ Map, Vertex };
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+pub struct Arena_Params
+{
+  /* Y is up. */
+  dimensions: math::Vec3i,
+  voxel_size: f32,
+
+  /* Terrain rests on a solid floor and rolls by up to amplitude. */
+  floor_height: i32,
+  terrain_amplitude: i32,
+
+  rooms: uint,
+  room_min_size: i32,
+  room_max_size: i32,
+  corridor_width: i32,
+  wall_height: i32,
+
+  pillars: uint,
+  spawn_count: uint,
+}
+
+impl Arena_Params
+{
+  pub fn new() -> Arena_Params
+  {
+    Arena_Params
+    {
+      dimensions: math::Vec3i::new(128, 40, 128),
+      voxel_size: 0.25,
+
+      floor_height: 2,
+      terrain_amplitude: 8,
+
+      rooms: 6,
+      room_min_size: 10,
+      room_max_size: 24,
+      corridor_width: 3,
+      wall_height: 6,
+
+      pillars: 12,
+      spawn_count: 8,
+    }
+  }
+}
+
+pub struct Arena
+{
+  map: @mut Map,
+  /* World-space positions just above the floor of the rooms. */
+  spawns: ~[math::Vec3f],
+}
+
+/* A rectangle of open floor, in grid cells on the XZ plane. */
+struct Room
+{
+  x: i32,
+  z: i32,
+  width: i32,
+  depth: i32,
+  floor: i32,
+}
+
+impl Room
+{
+  fn center(&self) -> (i32, i32)
+  { (self.x + (self.width / 2), self.z + (self.depth / 2)) }
+}
+
+/* What each column of the arena holds. */
+enum Column
+{
+  Terrain_Column,
+  Open_Column,
+  Wall_Column,
+  Pillar_Column,
+}
+
+impl Map
+{
+  pub fn new_arena(seed: u64, params: &Arena_Params) -> Result<Arena, ~str>
+  {
+    let dims = params.dimensions;
+    if dims.x < 8 || dims.y < 4 || dims.z < 8
+    { return Err(format!("Arena dimensions {} are too small", dims.to_str())); }
+    if params.room_min_size < 3 || params.room_max_size < params.room_min_size
+    { return Err(~"Invalid arena room sizes"); }
+    if !(params.voxel_size > 0.0)
+    { return Err(~"Invalid voxel size"); }
+
+    let mut rng = math::Random::new(seed);
+    let column_count = (dims.x * dims.z) as uint;
+    let column = |x: i32, z: i32| { ((z * dims.x) + x) as uint };
+    let max_height = dims.y - 1;
+
+    /* Rolling terrain from a few octaves of value noise. */
+    let mut heights = vec::from_elem(column_count, 0i32);
+    let mut kinds = vec::from_elem(column_count, Terrain_Column);
+    for z in range(0, dims.z)
+    {
+      for x in range(0, dims.x)
+      {
+        let n = fractal_noise(seed, (x as f32) / 32.0, (z as f32) / 32.0);
+        let h = params.floor_height + ((n * (params.terrain_amplitude as f32)) as i32);
+        heights[column(x, z)] = h.clamp(&1, &max_height);
+      }
+    }
+
+    /* Rooms are flattened to the terrain height at their center. */
+    let mut rooms: ~[Room] = ~[];
+    for _ in range(0, params.rooms)
+    {
+      let width = rng.range_i32(params.room_min_size, params.room_max_size + 1);
+      let depth = rng.range_i32(params.room_min_size, params.room_max_size + 1);
+      if width + 2 >= dims.x || depth + 2 >= dims.z
+      { continue; }
+
+      let x = rng.range_i32(1, dims.x - width - 1);
+      let z = rng.range_i32(1, dims.z - depth - 1);
+      let floor = heights[column(x + (width / 2), z + (depth / 2))];
+      rooms.push(Room { x: x, z: z, width: width, depth: depth, floor: floor });
+    }
+
+    /* Corridors join each room to the next with an L, ramping
+     * between their floors. They're laid before the rooms so that
+     * the rooms' floors win wherever they overlap. */
+    for i in range(1, rooms.len())
+    {
+      let (ax, az) = rooms[i - 1].center();
+      let (bx, bz) = rooms[i].center();
+      let (floor_a, floor_b) = (rooms[i - 1].floor, rooms[i].floor);
+
+      let mut path = ~[];
+      let step_x = if bx > ax { 1 } else { -1 };
+      let mut x = ax;
+      while x != bx
+      { path.push((x, az)); x += step_x; }
+      let step_z = if bz > az { 1 } else { -1 };
+      let mut z = az;
+      while z != bz
+      { path.push((bx, z)); z += step_z; }
+      path.push((bx, bz));
+
+      let half = params.corridor_width / 2;
+      for (step, &(px, pz)) in path.iter().enumerate()
+      {
+        let t = (step as f32) / (cmp::max(path.len() - 1, 1) as f32);
+        let floor = (floor_a as f32) + (((floor_b - floor_a) as f32) * t);
+        for oz in range(pz - half, pz + half + 1)
+        {
+          for ox in range(px - half, px + half + 1)
+          {
+            if ox < 0 || oz < 0 || ox >= dims.x || oz >= dims.z
+            { continue; }
+            heights[column(ox, oz)] = floor.round() as i32;
+            kinds[column(ox, oz)] = Open_Column;
+          }
+        }
+      }
+    }
+    for room in rooms.iter()
+    {
+      for z in range(room.z, room.z + room.depth)
+      {
+        for x in range(room.x, room.x + room.width)
+        {
+          heights[column(x, z)] = room.floor;
+          kinds[column(x, z)] = Open_Column;
+        }
+      }
+    }
+
+    /* Walls surround every open column which borders terrain. Where
+     * corridors meet rooms, the open floor leaves a doorway. */
+    let mut walls = ~[];
+    for z in range(0, dims.z)
+    {
+      for x in range(0, dims.x)
+      {
+        match kinds[column(x, z)]
+        {
+          Open_Column => { continue; }
+          _ => { }
+        }
+
+        let mut top = -1;
+        for nz in range(z - 1, z + 2)
+        {
+          for nx in range(x - 1, x + 2)
+          {
+            if nx < 0 || nz < 0 || nx >= dims.x || nz >= dims.z
+            { continue; }
+            match kinds[column(nx, nz)]
+            {
+              Open_Column => { top = cmp::max(top, heights[column(nx, nz)] + params.wall_height); }
+              _ => { }
+            }
+          }
+        }
+        if top >= 0
+        { walls.push((x, z, top)); }
+      }
+    }
+    for &(x, z, top) in walls.iter()
+    {
+      heights[column(x, z)] = cmp::max(heights[column(x, z)], top).clamp(&1, &max_height);
+      kinds[column(x, z)] = Wall_Column;
+    }
+
+    /* Pillars stand out in the open terrain, away from the rooms. */
+    let mut placed = 0;
+    let mut attempts = 0;
+    while placed < params.pillars && attempts < params.pillars * 16
+    {
+      attempts += 1;
+      let x = rng.range_i32(0, dims.x - 2);
+      let z = rng.range_i32(0, dims.z - 2);
+      let corners = [ (x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1) ];
+      let mut blocked = false;
+      for &(px, pz) in corners.iter()
+      {
+        match kinds[column(px, pz)]
+        {
+          Terrain_Column => { }
+          _ => { blocked = true; }
+        }
+      }
+      if blocked
+      { continue; }
+
+      let top = (heights[column(x, z)] + rng.range_i32(params.wall_height, params.wall_height * 3)).clamp(&1, &max_height);
+      for &(px, pz) in corners.iter()
+      {
+        heights[column(px, pz)] = top;
+        kinds[column(px, pz)] = Pillar_Column;
+      }
+      placed += 1;
+    }
+
+    /* Fill each column solid, up to its height. */
+    let mut voxels = ~[];
+    for z in range(0, dims.z)
+    {
+      for x in range(0, dims.x)
+      {
+        let kind = kinds[column(x, z)];
+        let height = heights[column(x, z)].clamp(&1, &max_height);
+        for y in range(0, height + 1)
+        {
+          let base = match kind
+          {
+            Terrain_Column if y == height => math::Vec3f::new(0.30, 0.55, 0.25),
+            Terrain_Column => math::Vec3f::new(0.45, 0.35, 0.25),
+            Open_Column => math::Vec3f::new(0.55, 0.55, 0.55),
+            Wall_Column => math::Vec3f::new(0.60, 0.45, 0.35),
+            Pillar_Column => math::Vec3f::new(0.70, 0.70, 0.65),
+          };
+
+          /* A touch of grain, so that the surfaces don't look flat. */
+          let grain = 0.9 + (0.1 * hash_f32(seed, x, y, z));
+          voxels.push(Vertex
+          {
+            position: math::Vec3f::new(x as f32, y as f32, z as f32),
+            color: base * grain,
+          });
+        }
+      }
+    }
+
+    /* The arena is centered horizontally and rests on y = 0. */
+    let origin = math::Vec3f::new(-(dims.x as f32) * params.voxel_size / 2.0,
+                                  0.0,
+                                  -(dims.z as f32) * params.voxel_size / 2.0);
+    let map = Map::new_with_voxels(dims, params.voxel_size, origin, voxels);
+    if map.is_err()
+    { return Err(map.unwrap_err()); }
+
+    /* Spawn in the rooms, round robin, at random spots off of the walls. */
+    let mut spawns = ~[];
+    for i in range(0, params.spawn_count)
+    {
+      let (x, z, floor) = if rooms.len() > 0
+      {
+        let room = &rooms[i % rooms.len()];
+        (rng.range_i32(room.x + 1, room.x + room.width - 1),
+         rng.range_i32(room.z + 1, room.z + room.depth - 1),
+         room.floor)
+      }
+      else
+      {
+        let x = rng.range_i32(0, dims.x);
+        let z = rng.range_i32(0, dims.z);
+        (x, z, heights[column(x, z)])
+      };
+
+      spawns.push(origin + (math::Vec3f::new((x as f32) + 0.5,
+                                             (floor as f32) + 2.0,
+                                             (z as f32) + 0.5) * params.voxel_size));
+    }
+
+    log_info!("Generated arena {} with {} rooms, {} pillars, and {} spawns",
+              seed, rooms.len(), placed, spawns.len());
+
+    Ok(Arena { map: map.unwrap(), spawns: spawns })
+  }
+}
+
+/* A repeatable value in [0, 1) for the lattice point. The
+ * coordinates are packed into 21 bits each and folded into the
+ * generator's seed, so points within a million voxels of the
+ * origin never share a seed. */
+fn hash_f32(key: u64, x: i32, y: i32, z: i32) -> f32
+{
+  let bits = |v: i32| { (v as u64) & 0x1FFFFF };
+  let mut rng = math::Random::new(key ^ bits(x) ^ (bits(y) << 21) ^ (bits(z) << 42));
+  rng.next_f32()
+}
+
+/* Smoothly interpolated lattice noise, in [0, 1]. */
+fn value_noise(key: u64, x: f32, z: f32) -> f32
+{
+  let (x0, z0) = (x.floor() as i32, z.floor() as i32);
+  let (tx, tz) = (x - (x0 as f32), z - (z0 as f32));
+  let (sx, sz) = (tx * tx * (3.0 - (2.0 * tx)), tz * tz * (3.0 - (2.0 * tz)));
+
+  let a = hash_f32(key, x0, 0, z0);
+  let b = hash_f32(key, x0 + 1, 0, z0);
+  let c = hash_f32(key, x0, 0, z0 + 1);
+  let d = hash_f32(key, x0 + 1, 0, z0 + 1);
+
+  let top = a + ((b - a) * sx);
+  let bottom = c + ((d - c) * sx);
+  top + ((bottom - top) * sz)
+}
+
+/* Three octaves of value noise, each at twice the frequency and
+ * half the weight of the last; normalized to [0, 1]. */
+fn fractal_noise(seed: u64, x: f32, z: f32) -> f32
+{
+  let mut sum = 0.0;
+  let mut weight = 1.0;
+  let mut total = 0.0;
+  let mut freq = 1.0;
+  for octave in range(0u64, 3u64)
+  {
+    /* A key drawn per octave, so the octaves don't line up. */
+    let mut rng = math::Random::new(seed + octave);
+    let key = rng.next_u64();
+    sum += value_noise(key, x * freq, z * freq) * weight;
+    total += weight;
+    weight *= 0.5;
+    freq *= 2.0;
+  }
+  sum / total
+}
@@ -0,0 +1,158 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/heightmap.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Importer of grayscale heightmap images
+      as solid voxel terrain, optionally colored
+      by a second image of the same size. This
+      doesn't touch GL, so the server can use it.
+*/
+
+use stb_image;
+use math;
+use super::{ Map, Vertex };
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+/* Pixels as loaded by stb_image, with depth bytes per pixel. */
+struct Image
+{
+  width: uint,
+  height: uint,
+  depth: uint,
+  data: ~[u8],
+}
+
+impl Image
+{
+  /* The average of the pixel's color channels, in [0, 1]. */
+  fn gray(&self, x: uint, y: uint) -> f32
+  {
+    let start = ((y * self.width) + x) * self.depth;
+    let channels = if self.depth >= 3 { 3 } else { 1 };
+    let mut sum = 0.0;
+    for c in range(0, channels)
+    { sum += self.data[start + c] as f32; }
+    sum / ((channels as f32) * 255.0)
+  }
+
+  fn color(&self, x: uint, y: uint) -> math::Vec3f
+  {
+    let start = ((y * self.width) + x) * self.depth;
+    if self.depth >= 3
+    {
+      math::Vec3f::new(self.data[start] as f32 / 255.0,
+                       self.data[start + 1] as f32 / 255.0,
+                       self.data[start + 2] as f32 / 255.0)
+    }
+    else
+    {
+      let g = self.data[start] as f32 / 255.0;
+      math::Vec3f::new(g, g, g)
+    }
+  }
+}
+
+impl Map
+{
+  /* Each pixel becomes a column of voxels, horizontal_scale world
+   * units across, which reaches vertical_range world units high for
+   * white. Image rows run along Z and columns along X; Y is up. */
+  pub fn new_from_heightmap(height_file: &str, color_file: Option<&str>,
+                            horizontal_scale: f32, vertical_range: f32) -> Result<@mut Map, ~str>
+  {
+    if !(horizontal_scale > 0.0)
+    { return Err(~"Invalid horizontal scale"); }
+    if !(vertical_range >= 0.0)
+    { return Err(~"Invalid vertical range"); }
+
+    let heights = load_image(height_file);
+    if heights.is_err()
+    { return Err(heights.unwrap_err()); }
+    let heights = heights.unwrap();
+
+    let colors = match color_file
+    {
+      Some(file) =>
+      {
+        let colors = load_image(file);
+        if colors.is_err()
+        { return Err(colors.unwrap_err()); }
+        let colors = colors.unwrap();
+        if colors.width != heights.width || colors.height != heights.height
+        {
+          return Err(format!("Color image {} is {}x{}, but the heightmap is {}x{}",
+                             file, colors.width, colors.height, heights.width, heights.height));
+        }
+        Some(colors)
+      }
+      None => None,
+    };
+
+    let voxel_size = horizontal_scale;
+    let max_height = (vertical_range / voxel_size).ceil() as i32;
+    let dimensions = math::Vec3i::new(heights.width as i32, max_height + 1, heights.height as i32);
+
+    let mut voxels = ~[];
+    for z in range(0, heights.height)
+    {
+      for x in range(0, heights.width)
+      {
+        let gray = heights.gray(x, z);
+        let top = ((gray * vertical_range) / voxel_size).round() as i32;
+        let color = match colors
+        {
+          Some(ref colors) => colors.color(x, z),
+          /* Without a color image, shade by height. */
+          None => math::Vec3f::new(0.25, 0.25, 0.25) + (math::Vec3f::new(0.75, 0.75, 0.75) * gray),
+        };
+
+        for y in range(0, top.clamp(&0, &max_height) + 1)
+        {
+          voxels.push(Vertex
+          {
+            position: math::Vec3f::new(x as f32, y as f32, z as f32),
+            color: color,
+          });
+        }
+      }
+    }
+
+    /* The terrain is centered horizontally and rests on y = 0. */
+    let origin = math::Vec3f::new(-(dimensions.x as f32) * voxel_size / 2.0,
+                                  0.0,
+                                  -(dimensions.z as f32) * voxel_size / 2.0);
+
+    log_debug!("Importing {}x{} heightmap {} as {} voxels",
+               heights.width, heights.height, height_file, voxels.len());
+    Map::new_with_voxels(dimensions, voxel_size, origin, voxels)
+  }
+}
+
+fn load_image(file: &str) -> Result<Image, ~str>
+{
+  match stb_image::image::load(file.to_owned())
+  {
+    stb_image::image::ImageU8(image) =>
+    {
+      if image.width == 0 || image.height == 0 || image.depth == 0
+      { return Err(format!("Empty image {}", file)); }
+
+      Ok(Image
+      {
+        width: image.width,
+        height: image.height,
+        depth: image.depth,
+        data: image.data,
+      })
+    }
+    _ => Err(format!("Failed to load image {}", file)),
+  }
+}
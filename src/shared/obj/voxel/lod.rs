@@ -0,0 +1,162 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/lod.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Downsampled levels of detail of the voxel
+      map, so that distant regions can be drawn
+      coarsely rather than being cut off.
+*/
+
+use std::vec;
+use math;
+use super::{ Map, Vertex, Visible };
+use super::map::{ grid_in_bounds, grid_index };
+
+/* Levels at 2x, 4x, and 8x the voxel size. */
+pub static LOD_LEVELS: uint = 3;
+
+/* Distance, in full-resolution voxels, within which the map is drawn
+ * at full resolution. Each level reaches twice as far as the last. */
+static LOD_RANGE: f32 = 64.0;
+
+pub struct Lod
+{
+  /* Full-resolution voxels per one of these, along each axis. */
+  scale: i32,
+  dimensions: math::Vec3i,
+  voxel_size: f32,
+
+  /* Laid out just like Map::states and Map::voxels; voxel
+   * positions are cells in this level's grid. */
+  states: ~[u32],
+  voxels: ~[Vertex],
+}
+
+impl Map
+{
+  /* Rebuilds every level from the full-resolution states. Maps
+   * don't do this on their own; call it before using lod_level. */
+  pub fn build_lods(&mut self) -> bool
+  {
+    let lods = match self.states
+    {
+      Some(ref states) =>
+      {
+        let mut lods: ~[Lod] = ~[];
+        for level in range(0, LOD_LEVELS)
+        {
+          let lod = if level == 0
+          { downsample(self.dimensions, *states, self.voxels, 2, self.voxel_size * 2.0) }
+          else
+          {
+            let prev = &lods[level - 1];
+            downsample(prev.dimensions, prev.states, prev.voxels, prev.scale * 2, prev.voxel_size * 2.0)
+          };
+          lods.push(lod);
+        }
+        lods
+      }
+      None => { self.error = ~"Voxel states are unavailable"; return false; }
+    };
+
+    self.lods = lods;
+    true
+  }
+
+  /* Which level to draw the cells from start to end (inclusive) with, as
+   * seen from the camera. Zero is full resolution; n is lods[n - 1].
+   * Without built levels, everything is full resolution. */
+  pub fn lod_level(&self, start: &math::Vec3i, end: &math::Vec3i, camera: &math::Vec3f) -> uint
+  {
+    /* Distance, in voxels, from the camera to the region's bounds. */
+    let pos = self.world_to_grid(camera);
+    let mut dist_sq = 0.0f32;
+    for q in range(0u, 3u)
+    {
+      let low = start[q] as f32;
+      let high = (end[q] + 1) as f32;
+      let d = if pos[q] < low
+      { low - pos[q] }
+      else if pos[q] > high
+      { pos[q] - high }
+      else
+      { 0.0 };
+      dist_sq += d * d;
+    }
+    let dist = dist_sq.sqrt();
+
+    let mut level = 0;
+    let mut reach = LOD_RANGE;
+    while level < self.lods.len() && dist > reach
+    {
+      level += 1;
+      reach *= 2.0;
+    }
+    level
+  }
+}
+
+/* Halves the grid along each axis. Each 2x2x2 block becomes a voxel when
+ * at least half of it is occupied; ties go to solid so that walls a
+ * single voxel thick survive. The color is the average of the block. */
+fn downsample(dims: math::Vec3i, states: &[u32], voxels: &[Vertex],
+              scale: i32, voxel_size: f32) -> Lod
+{
+  let coarse = math::Vec3i::new((dims.x + 1) / 2, (dims.y + 1) / 2, (dims.z + 1) / 2);
+  let mut out_states = vec::from_elem((coarse.x * coarse.y * coarse.z) as uint, 0u32);
+  let mut out_voxels = ~[];
+
+  for z in range(0, coarse.z)
+  { for y in range(0, coarse.y)
+    { for x in range(0, coarse.x)
+      {
+        let mut filled = 0;
+        let mut total = 0;
+        let mut color = math::Vec3f::zero();
+        for dz in range(0, 2)
+        { for dy in range(0, 2)
+          { for dx in range(0, 2)
+            {
+              let fine = math::Vec3i::new((x * 2) + dx, (y * 2) + dy, (z * 2) + dz);
+              if !grid_in_bounds(&dims, &fine)
+              { continue; }
+
+              total += 1;
+              let state = states[grid_index(&dims, &fine)];
+              if (state & Visible) != 0
+              {
+                filled += 1;
+                color = color + voxels[state & !Visible].color;
+              }
+            }
+          }
+        }
+
+        if filled == 0 || (filled * 2) < total
+        { continue; }
+
+        let index = grid_index(&coarse, &math::Vec3i::new(x, y, z));
+        out_states[index] = out_voxels.len() as u32;
+        out_states[index] |= Visible;
+        out_voxels.push(Vertex
+        {
+          position: math::Vec3f::new(x as f32, y as f32, z as f32),
+          color: color * (1.0 / (filled as f32)),
+        });
+      }
+    }
+  }
+
+  Lod
+  {
+    scale: scale,
+    dimensions: coarse,
+    voxel_size: voxel_size,
+    states: out_states,
+    voxels: out_voxels,
+  }
+}
@@ -14,7 +14,7 @@ use std::{ vec, cmp };
 use extra;
 use math;
 use primitive::Triangle;
-use super::{ Vertex, Visible };
+use super::{ Vertex, Visible, Mode, Lod, Occlusion };
 use log::Log;
 
 #[macro_escape]
@@ -23,413 +23,569 @@ mod macros;
 
 pub struct Map
 {
-  resolution: u32,
+  /* Number of voxels along each axis. */
+  dimensions: math::Vec3i,
   voxel_size: f32,
+  /* World-space position of the grid's minimum corner. */
+  origin: math::Vec3f,
 
   states: Option<~[u32]>,
   voxels: ~[Vertex],
+  /* Baked per-face AO, parallel to voxels. */
+  occlusion: ~[Occlusion],
+  /* Coarser levels, from 2x the voxel size up. These cost a pass
+   * over the whole grid, and nothing draws them yet, so they're
+   * only built when build_lods is called. */
+  lods: ~[Lod],
+  /* Bumped whenever voxels are added, removed, or renumbered, so
+   * that anything which uploaded them knows to do so again. */
+  revision: u32,
+  /* The range of state indices, as [start, end), changed since
+   * take_dirty was last called; copies of the states only need
+   * that much copied again. */
+  dirty: Option<(uint, uint)>,
   error: ~str,
 }
 
 impl Map
 {
-  pub fn new(tris: &[Triangle], res: u32) -> Result<@mut Map, ~str>
+  /* Fits res voxels across the largest extent of the mesh. */
+  pub fn new(tris: &[Triangle], res: u32, mode: Mode) -> Result<@mut Map, ~str>
   {
-    let map = @mut Map
+    let voxel_size = fit_voxel_size(tris, res);
+    if voxel_size.is_err()
+    { return Err(voxel_size.unwrap_err()); }
+
+    Map::new_with_voxel_size(tris, voxel_size.unwrap(), mode)
+  }
+
+  /* Sizes each axis of the grid independently to fit the mesh. */
+  pub fn new_with_voxel_size(tris: &[Triangle], voxel_size: f32, mode: Mode) -> Result<@mut Map, ~str>
+  {
+    let map = Map::new_empty(voxel_size);
+    if !map.voxelize(tris, mode, false)
+    { return Err(map.error.clone()); }
+
+    Ok(map)
+  }
+
+  /* Voxelizes like new, but tests every triangle against every voxel
+   * in the grid with the mode's brute-force reference. This is far too
+   * slow for real maps; it exists to check the fast path against. */
+  pub fn new_reference(tris: &[Triangle], res: u32, mode: Mode) -> Result<@mut Map, ~str>
+  {
+    let voxel_size = fit_voxel_size(tris, res);
+    if voxel_size.is_err()
+    { return Err(voxel_size.unwrap_err()); }
+
+    let map = Map::new_empty(voxel_size.unwrap());
+    if !map.voxelize(tris, mode, true)
+    { return Err(map.error.clone()); }
+
+    Ok(map)
+  }
+
+  fn new_empty(voxel_size: f32) -> @mut Map
+  {
+    @mut Map
     {
-      resolution: res,
-      voxel_size: 0.0,
+      dimensions: math::Vec3i::zero(),
+      voxel_size: voxel_size,
+      origin: math::Vec3f::zero(),
 
       states: None,
       voxels: ~[],
+      occlusion: ~[],
+      lods: ~[],
+      revision: 0,
+      dirty: None,
       error: ~"",
-    };
+    }
+  }
+
+  /* Builds a map directly from voxels, skipping voxelization. Each
+   * voxel's position is its grid cell; the first voxel in a cell wins. */
+  pub fn new_with_voxels(dimensions: math::Vec3i, voxel_size: f32, origin: math::Vec3f,
+                         voxels: &[Vertex]) -> Result<@mut Map, ~str>
+  {
+    if dimensions.x < 1 || dimensions.y < 1 || dimensions.z < 1
+    { return Err(format!("Invalid grid dimensions {}", dimensions.to_str())); }
+    if !(voxel_size > 0.0)
+    { return Err(~"Invalid voxel size"); }
+
+    let state_count = (dimensions.x * dimensions.y * dimensions.z) as uint;
+    let mut states = vec::from_elem(state_count, 0u32); /* Invisible. */
+    let mut enabled = vec::with_capacity(voxels.len());
+    for vox in voxels.iter()
+    {
+      let cell = vox.position.to_i();
+      if !grid_in_bounds(&dimensions, &cell)
+      { return Err(format!("Voxel {} is outside of the grid", vox.position.to_str())); }
+
+      let index = grid_index(&dimensions, &cell);
+      if (states[index] & Visible) != 0
+      { continue; }
+
+      states[index] = enabled.len() as u32;
+      states[index] |= Visible;
+      enabled.push(*vox);
+    }
+    log_debug!("Enabled {} of {} voxels", enabled.len(), state_count);
 
-    if !map.voxelize(tris)
+    let map = @mut Map
+    {
+      dimensions: dimensions,
+      voxel_size: voxel_size,
+      origin: origin,
+
+      states: Some(states),
+      voxels: enabled,
+      occlusion: ~[],
+      lods: ~[],
+      revision: 0,
+      dirty: None,
+      error: ~"",
+    };
+    if !map.bake_occlusion()
     { return Err(map.error.clone()); }
 
     Ok(map)
   }
 
-  fn voxelize(&mut self, tris: &[Triangle]) -> bool
+  /* Continuous grid coordinates of a world position, where each
+   * voxel is one unit across and cell boundaries are integers. */
+  pub fn world_to_grid(&self, pos: &math::Vec3f) -> math::Vec3f
+  { (*pos - self.origin) * (1.0 / self.voxel_size) }
+
+  /* The cell containing a world position. Positions on a boundary
+   * belong to the cell above it. The cell may be out of bounds. */
+  pub fn world_to_cell(&self, pos: &math::Vec3f) -> math::Vec3i
+  {
+    let grid = self.world_to_grid(pos);
+    grid.floor().to_i()
+  }
+
+  /* Cell (x, y, z) spans [origin + cell * voxel_size, origin + (cell + 1) * voxel_size)
+   * so its center is half of a voxel in from its minimum corner. Voxel
+   * positions, and the renderer's offsets, are cells; not centers. */
+  pub fn cell_to_world_center(&self, cell: &math::Vec3i) -> math::Vec3f
+  {
+    self.origin + (math::Vec3f::new((cell.x as f32) + 0.5,
+                                    (cell.y as f32) + 0.5,
+                                    (cell.z as f32) + 0.5) * self.voxel_size)
+  }
+
+  /* World-space minimum and maximum corners of the cell. */
+  pub fn cell_bounds(&self, cell: &math::Vec3i) -> (math::Vec3f, math::Vec3f)
+  {
+    let min = self.origin + (cell.to_f() * self.voxel_size);
+    (min, min + math::Vec3f::new(self.voxel_size, self.voxel_size, self.voxel_size))
+  }
+
+  pub fn in_bounds(&self, cell: &math::Vec3i) -> bool
+  { grid_in_bounds(&self.dimensions, cell) }
+
+  /* Index of the cell into states; the cell must be in bounds. */
+  pub fn cell_index(&self, cell: &math::Vec3i) -> uint
+  { grid_index(&self.dimensions, cell) }
+
+  pub fn index_to_cell(&self, index: uint) -> math::Vec3i
+  { grid_cell(&self.dimensions, index) }
+
+  /* The states changed since the last call, if any. */
+  pub fn take_dirty(&mut self) -> Option<(uint, uint)>
+  { self.dirty.take() }
+
+  fn voxelize(&mut self, tris: &[Triangle], mode: Mode, reference: bool) -> bool
   {
     /* Require at least one triangle. */
     if !(tris.len() >= 1)
     { self.error = ~"Invalid triangle count"; return false; }
+    if !(self.voxel_size > 0.0)
+    { self.error = ~"Invalid voxel size"; return false; }
 
-    log_info!("Voxelizing in {}x{}x{} grid",
-              self.resolution,
-              self.resolution,
-              self.resolution);
     log_debug!("Incoming triangles: {}", tris.len());
 
     /* Bounding box of vert dimensions. */
-    let mut min = math::Vec3f::new( tris[0].verts[0].position.x,
-                                    tris[0].verts[0].position.y, 
-                                    tris[0].verts[0].position.z);
-    let mut max = math::Vec3f::new( tris[0].verts[0].position.x,
-                                    tris[0].verts[0].position.y,
-                                    tris[0].verts[0].position.z);
-    for curr in tris.iter()
-    {
-      for vert in curr.verts.iter()
-      {
-        min.x = cmp::min(min.x, vert.position.x);
-        min.y = cmp::min(min.y, vert.position.y);
-        min.z = cmp::min(min.z, vert.position.z);
-
-        max.x = cmp::max(max.x, vert.position.x);
-        max.y = cmp::max(max.y, vert.position.y);
-        max.z = cmp::max(max.z, vert.position.z);
-      }
-    }
-    log_debug!("Min: {} Max: {}", min.to_str(), max.to_str());
-    let center = math::Vec3f::new(max.x - ((max.x - min.x) / 2.0),
-                                  max.y - ((max.y - min.y) / 2.0),
-                                  max.z - ((max.z - min.z) / 2.0));
-    log_debug!("Center of mesh is {}", center.to_str());
-
-    /* Calculate, given resolution (how many states across), the dimensions of a voxel. */
-    self.voxel_size = cmp::max( max.x - min.x,
-                                cmp::max(max.y - min.y, max.z - min.z)) / (self.resolution as f32);
+    let bounds = mesh_bounds(tris);
+    log_debug!("Min: {} Max: {}", bounds.min.to_str(), bounds.max.to_str());
     log_debug!("Voxel size is {}", self.voxel_size);
 
-    /* World space mid point of the grid. */
-    let mid_offset = (((self.resolution as f32) / 2.0) * self.voxel_size); 
-    log_debug!("Midpoint offset is {}", mid_offset);
+    /* The grid starts at the mesh's minimum corner and reaches just
+     * past its maximum, so that every vert lands within a voxel. */
+    self.origin = bounds.min;
+    self.dimensions = self.world_to_cell(&bounds.max) + math::Vec3i::new(1, 1, 1);
+    log_info!("Voxelizing in {}x{}x{} grid",
+              self.dimensions.x,
+              self.dimensions.y,
+              self.dimensions.z);
+    log_debug!("Grid origin is {}", self.origin.to_str());
 
     /* Create 3D array of states. */
-    self.states = Some(vec::with_capacity(((self.resolution + 1) as f32).pow(&3.0) as uint));
-    self.voxels = vec::with_capacity(self.states.get_mut_ref().len() / 2); /* Half is just a (generous) guess. */
-    for _z in range(0, self.resolution as uint) 
-    { for _y in range(0, self.resolution as uint)
-      { for _x in range(0, self.resolution as uint)
-        {
-          self.states.get_mut_ref().push(0); /* Invisible. */
-        }
-      }
-    }
-
-    /* Voxels are temporarily stored in a set, which will prevent the
-     * voxelization algorithm from adding any duplicated. We trade a bit
-     * of CPU to save some memory here. */
-    let mut voxels = extra::treemap::TreeSet::new();
+    let state_count = (self.dimensions.x * self.dimensions.y * self.dimensions.z) as uint;
+    self.states = Some(vec::from_elem(state_count, 0u32)); /* Invisible. */
+    self.voxels = vec::with_capacity(state_count / 2); /* Half is just a (generous) guess. */
+
+    /* Voxels are temporarily stored in a map, keyed by their state index,
+     * which will prevent the voxelization algorithm from adding any
+     * duplicates. Each entry accumulates the color of every triangle
+     * touching the voxel. We trade a bit of CPU to save some memory here. */
+    let mut samples = extra::treemap::TreeMap::new();
     for tri in tris.iter()
     {
       /* Calculate bounding box of the triangle. */
-      min = math::Vec3f::new(tri.verts[0].position.x, tri.verts[0].position.y, tri.verts[0].position.z);
-      max = math::Vec3f::new(tri.verts[0].position.x, tri.verts[0].position.y, tri.verts[0].position.z);
-      for vert in tri.verts.iter()
+      let tri_bounds = math::BB3::from_points([ tri.verts[0].position,
+                                                tri.verts[1].position,
+                                                tri.verts[2].position ]);
+
+      /* The range of voxels covered by the triangle's bounding box;
+       * the reference tests the whole grid, to not rely on it. */
+      let last = self.dimensions - math::Vec3i::new(1, 1, 1);
+      let start = self.world_to_cell(&tri_bounds.min);
+      let end = self.world_to_cell(&tri_bounds.max);
+      let mut start_voxels = math::Vec3i::new(start.x.clamp(&0, &last.x),
+                                              start.y.clamp(&0, &last.y),
+                                              start.z.clamp(&0, &last.z));
+      let mut end_voxels = math::Vec3i::new(end.x.clamp(&0, &last.x),
+                                            end.y.clamp(&0, &last.y),
+                                            end.z.clamp(&0, &last.z));
+      if reference
       {
-        /* Adjust by half of a voxel to account for voxel centering. */
-        min.x = cmp::min(min.x, vert.position.x - (self.voxel_size / 2.0));
-        min.y = cmp::min(min.y, vert.position.y - (self.voxel_size / 2.0));
-        min.z = cmp::min(min.z, vert.position.z - (self.voxel_size / 2.0));
-
-        max.x = cmp::max(max.x, vert.position.x - (self.voxel_size / 2.0));
-        max.y = cmp::max(max.y, vert.position.y - (self.voxel_size / 2.0));
-        max.z = cmp::max(max.z, vert.position.z - (self.voxel_size / 2.0));
+        start_voxels = math::Vec3i::zero();
+        end_voxels = last;
       }
 
-      /* The dimensions (in voxels) of the triangle's bounding box. */
-      let mut vox_amount = math::Vec3i::new((((max.x - min.x) / self.voxel_size)).ceil() as i32,
-                                            (((max.y - min.y) / self.voxel_size)).ceil() as i32,
-                                            (((max.z - min.z) / self.voxel_size)).ceil() as i32);
-      if vox_amount.x < 1
-      { vox_amount.x = 1; }
-      if vox_amount.y < 1
-      { vox_amount.y = 1; }
-      if vox_amount.z < 1
-      { vox_amount.z = 1; }
-      //log_debug!("[Per voxel] Checking {} surrounding states with SAT", vox_amount.to_str());
-
-      /* Get the starting indices of the triangle's bounding box. */
-      let start_voxels = math::Vec3i::new( ((min.x - -mid_offset) / self.voxel_size) as i32, 
-                                      ((min.y - -mid_offset) / self.voxel_size) as i32,
-                                      ((min.z - -mid_offset) / self.voxel_size) as i32);
-
       /* Test intersection with each accepted voxel. */
-      for z in range(start_voxels.z, start_voxels.z + vox_amount.z)
-      { for y in range(start_voxels.y, start_voxels.y + vox_amount.y)
-        { for x in range(start_voxels.x, start_voxels.x + vox_amount.x)
+      for z in range(start_voxels.z, end_voxels.z + 1)
+      { for y in range(start_voxels.y, end_voxels.y + 1)
+        { for x in range(start_voxels.x, end_voxels.x + 1)
           {
             /* Check for intersection. */
-            let c = math::Vec3f::new( ((x as f32 - (self.resolution as f32 / 2.0)) * self.voxel_size) + (self.voxel_size / 2.0), 
-                                ((y as f32 - (self.resolution as f32 / 2.0)) * self.voxel_size) + (self.voxel_size / 2.0),
-                                ((z as f32 - (self.resolution as f32 / 2.0)) * self.voxel_size) + (self.voxel_size / 2.0));
-            if tri_cube_intersect(c, self.voxel_size, tri)
+            let cell = math::Vec3i::new(x, y, z);
+            let c = self.cell_to_world_center(&cell);
+            let hit = if reference
+            { mode.intersects_reference(tri, c, self.voxel_size) }
+            else
+            { mode.intersects(tri, c, self.voxel_size) };
+            if hit
             {
-              /* Calculate the average color from all three verts. */
-              let av_color = math::Vec3f::new
-              (
-                ((tri.verts[0].color.x + tri.verts[1].color.x + tri.verts[2].color.x) / 3.0) as f32 / 255.0,
-                ((tri.verts[0].color.y + tri.verts[1].color.y + tri.verts[2].color.y) / 3.0) as f32 / 255.0,
-                ((tri.verts[0].color.z + tri.verts[1].color.z + tri.verts[2].color.z) / 3.0) as f32 / 255.0
-              );
-
-              /* Enable some debug rendering of invalid voxels. */
-              let col = if x >= self.resolution as i32 || y >= self.resolution as i32 || z >= self.resolution as i32
-              { math::Vec3f::new(1.0, 0.0, 0.0) }
-              else if x < 0 || y < 0 || z < 0
-              { math::Vec3f::new(1.0, 0.0, 0.0) }
-              else
-              { av_color };
-
-              /* We have intersection; add a reference to this voxel to the index map. */
-              voxels.insert(
-              Vertex
+              /* Color of the triangle at the point closest to the voxel's center,
+               * weighted by how much of the triangle lies within the voxel. */
+              let color = sample_color(tri, &c);
+              let weight = coverage(tri, &c, self.voxel_size / 2.0) + MIN_COVERAGE;
+
+              /* We have intersection; add this triangle's contribution to the voxel. */
+              let index = self.cell_index(&cell);
+              let found = match samples.find_mut(&index)
               {
-                position: math::Vec3f::new( x as f32 - (self.resolution / 2) as f32, 
-                                            y as f32 - (self.resolution / 2) as f32,
-                                            z as f32 - (self.resolution / 2) as f32), 
-                color: col
-              });
+                Some(sample) =>
+                {
+                  sample.color = sample.color + (color * weight);
+                  sample.weight += weight;
+                  true
+                }
+                None => { false }
+              };
+              if !found
+              { samples.insert(index, Sample { color: color * weight, weight: weight }); }
             }
           }
         }
       }
     }
 
-    /* Now that all of the voxels have been put in the set, we
+    /* Now that all of the voxels have been put in the map, we
      * can pull them out and store them in a contiguous structure.
      * Otherwise, giving the voxels to OpenGL would be difficult. */
-    for vox in voxels.iter()
+    for (index, sample) in samples.iter()
     {
+      let index = *index;
+
       /* Determine the voxel-space position. */
-      let x = (vox.position.x + (self.resolution / 2) as f32) as i32;
-      let y = (vox.position.y + (self.resolution / 2) as f32) as i32;
-      let z = (vox.position.z + (self.resolution / 2) as f32) as i32;
-      log_assert!(x >= 0 && y >= 0 && z >= 0);
-      log_assert!(x < self.resolution as i32 &&
-                  y < self.resolution as i32 &&
-                  z < self.resolution as i32);
+      let cell = self.index_to_cell(index);
+      log_assert!(self.in_bounds(&cell));
 
       log_assert!(self.states.is_some());
       let states = self.states.get_mut_ref();
-
-      /* Calculate the index of this voxel in the state grid. */
-      let index = (z * ((self.resolution * self.resolution) as i32)) + (y * (self.resolution as i32)) + x;
-      log_assert!(index < states.len() as i32);
+      log_assert!(index < states.len());
 
       /* Update the state grid with this voxel's data. */
       states[index] = self.voxels.len() as u32;
       states[index] |= Visible;
 
       /* Move this voxel into contiguous memory. */
-      self.voxels.push(*vox);
+      self.voxels.push(Vertex
+      {
+        position: cell.to_f(),
+        color: sample.color * (1.0 / (sample.weight * 255.0))
+      });
     }
 
     log_debug!("Enabled {} of {} voxels", self.voxels.len(), self.states.get_mut_ref().len());
 
-    true
+    self.bake_occlusion()
   }
-}
 
-fn tri_cube_intersect(box_center: math::Vec3f, box_size: f32, tri: &Triangle) -> bool
-{
-  let _v0;
-  let _v1;
-  let _v2;
-  let mut _min;
-  let mut _max;
-  let mut _p0 = 0.0;
-  let mut _p1 = 0.0;
-  let mut _p2 = 0.0;
-  let mut _rad;
-  let mut _fex;
-  let mut _fey;
-  let mut _fez;
-  let _normal;
-  let _e0;
-  let _e1;
-  let _e2;
-
-  macro_rules! find_min_max
-  (
-    ($x0:expr, $x1:expr, $x2:expr) =>
-    (
-      {
-        _min = $x0;
-        _max = $x0;
+  /* Number of cells which are visible in only one of the two maps,
+   * such as a map and its new_reference counterpart. */
+  pub fn count_mismatches(&self, other: &Map) -> Result<uint, ~str>
+  {
+    if self.dimensions != other.dimensions
+    { return Err(~"Maps have different dimensions"); }
 
-        if($x1 < _min){ _min = $x1; }
-        if($x1 > _max){ _max = $x1; }
-        if($x2 < _min){ _min = $x2; }
-        if($x2 > _max){ _max = $x2; }
-      }
-    )
-  )
-
-  /*======================== X-tests ========================*/
-  macro_rules! axis_test_x01
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
+    match (&self.states, &other.states)
+    {
+      (&Some(ref states), &Some(ref other_states)) =>
       {
-        _p0 = $a * _v0.y - $b * _v0.z;
-        _p2 = $a * _v2.y - $b * _v2.z;
-        if _p0 < _p2  { _min = _p0; _max = _p2; } else { _min = _p2; _max = _p0; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad  { return false; }
+        let mut count = 0;
+        for (a, b) in states.iter().zip(other_states.iter())
+        {
+          if (*a & Visible) != (*b & Visible)
+          { count += 1; }
+        }
+        Ok(count)
       }
-    )
-  )
+      _ => { Err(~"Voxel states are unavailable") }
+    }
+  }
+}
 
-  macro_rules! axis_test_x2
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
-      {
-        _p0 = $a * _v0.y - $b * _v0.z;
-        _p1 = $a * _v1.y - $b * _v1.z;
-        if _p0 < _p1 { _min = _p0; _max = _p1; } else { _min = _p1; _max = _p0; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad { return false; }
-      }
-    )
-  )
+/* The cell helpers, for grids other than a map's own (such as LODs)
+ * or for when the map itself can't be borrowed. States are laid out
+ * with X varying fastest, then Y, then Z. */
+pub fn grid_in_bounds(dims: &math::Vec3i, cell: &math::Vec3i) -> bool
+{
+  cell.x >= 0 && cell.y >= 0 && cell.z >= 0 &&
+  cell.x < dims.x && cell.y < dims.y && cell.z < dims.z
+}
 
-  /*======================== Y-tests ========================*/
+pub fn grid_index(dims: &math::Vec3i, cell: &math::Vec3i) -> uint
+{
+  log_assert!(grid_in_bounds(dims, cell));
+  ((cell.z * dims.x * dims.y) + (cell.y * dims.x) + cell.x) as uint
+}
 
-  macro_rules! axis_test_y02
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
-      {
-        _p0 = -$a * _v0.x + $b * _v0.z;
-        _p2 = -$a * _v2.x + $b * _v2.z;
-        if _p0 < _p2 { _min = _p0; _max = _p2; } else { _min = _p2; _max = _p0; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad { return false; }
-      }
-    )
-  )
+pub fn grid_cell(dims: &math::Vec3i, index: uint) -> math::Vec3i
+{
+  let index = index as i32;
+  math::Vec3i::new(index % dims.x, (index / dims.x) % dims.y, index / (dims.x * dims.y))
+}
 
-  macro_rules! axis_test_y1
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
-      {
-        _p0 = -$a * _v0.x + $b * _v0.z;
-        _p1 = -$a * _v1.x + $b * _v1.z;
-        if _p0 < _p1 { _min = _p0; _max = _p1; } else { _min = _p1; _max = _p0; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad { return false; }
-      }
-    )
-  )
+/* Widens a dirty range to take in [start, end). */
+pub fn grow_dirty(dirty: &mut Option<(uint, uint)>, start: uint, end: uint)
+{
+  *dirty = match *dirty
+  {
+    Some((low, high)) => Some((cmp::min(low, start), cmp::max(high, end))),
+    None => Some((start, end)),
+  };
+}
 
-  /*======================== Z-tests ========================*/
+/* Every triangle touching a voxel gets at least this much say in its
+ * color, even if it only grazes an edge or corner of the voxel. */
+static MIN_COVERAGE: f32 = 0.0001;
 
-  macro_rules! axis_test_z12
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
-      {
-        _p1 = $a * _v1.x - $b * _v1.y;
-        _p2 = $a * _v2.x - $b * _v2.y;
-        if _p2 < _p1 { _min = _p2; _max = _p1;} else { _min = _p1; _max = _p2; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad { return false; }
-      }
-    )
-  )
+/* Accumulated color of all triangles intersecting a voxel. */
+struct Sample
+{
+  color: math::Vec3f,
+  weight: f32,
+}
 
+/* The voxel size which fits res voxels across the mesh's largest extent. */
+fn fit_voxel_size(tris: &[Triangle], res: u32) -> Result<f32, ~str>
+{
+  /* Require at least one triangle. */
+  if !(tris.len() >= 1)
+  { return Err(~"Invalid triangle count"); }
 
-  macro_rules! axis_test_z0
-  (
-    ($a:expr, $b:expr, $fa:expr, $fb:expr) =>
-    (
-      {
-        _p0 = $a * _v0.x - $b * _v0.y;
-        _p1 = $a * _v1.x - $b * _v1.y;
-        if _p0 < _p1 { _min = _p0; _max = _p1; } else { _min = _p1; _max = _p0; }
-        _rad = $fa * box_size + $fb * box_size;
-        if _min > _rad || _max < -_rad { return false; }
-      }
-    )
-  )
-
-  /* Move everything so that the box's center is in (0, 0, 0). */
-  _v0 = tri.verts[0].position - box_center;
-  _v1 = tri.verts[1].position - box_center;
-  _v2 = tri.verts[2].position - box_center;
-
-  /* Computer triangle edges. */
-  _e0 = _v1 - _v0; /* Edge 0. */
-  _e1 = _v2 - _v1; /* Edge 1. */
-  _e2 = _v0 - _v2; /* Edge 2. */
-
-  //log_debug!("[Per voxel SAT] Testing bullet 3 edge 0");
-  /* Bullet 3. */
-  _fex = _e0.x.abs();
-  _fey = _e0.y.abs();
-  _fez = _e0.z.abs();
-  axis_test_x01!(_e0.z, _e0.y, _fez, _fey);
-  axis_test_y02!(_e0.z, _e0.x, _fez, _fex);
-  axis_test_z12!(_e0.y, _e0.x, _fey, _fex);
-
-  //log_debug!("[Per voxel SAT] Testing bullet 3 edge 1");
-  _fex = _e1.x.abs();
-  _fey = _e1.y.abs();
-  _fez = _e1.z.abs();
-  axis_test_x01!(_e1.z, _e1.y, _fez, _fey);
-  axis_test_y02!(_e1.z, _e1.x, _fez, _fex);
-  axis_test_z0!(_e1.y, _e1.x, _fey, _fex);
-
-  //log_debug!("[Per voxel SAT] Testing bullet 3 edge 2");
-  _fex = _e2.x.abs();
-  _fey = _e2.y.abs();
-  _fez = _e2.z.abs();
-  axis_test_x2!(_e2.z, _e2.y, _fez, _fey);
-  axis_test_y1!(_e2.z, _e2.x, _fez, _fex);
-  axis_test_z12!(_e2.y, _e2.x, _fey, _fex);
-
-  //log_debug!("[Per voxel SAT] Testing bullet 1");
-  /* Bullet 1. */
-  /* Test in X-direction */
-  find_min_max!(_v0.x, _v1.x, _v2.x);
-  if _min > box_size || _max < -box_size { return false; }
-
-  /* Test in Y-direction */
-  find_min_max!(_v0.y, _v1.y, _v2.y);
-  if _min > box_size || _max < -box_size { return false; }
-
-  /* Test in Z-direction */
-  find_min_max!(_v0.z, _v1.z, _v2.z);
-  if _min > box_size || _max < -box_size { return false; }
-
-  //log_debug!("[Per voxel SAT] Testing bullet 2");
-  /* Bullet 2. */
-  _normal = _e0.cross(&_e1);
-  plane_cube_intersect(&_normal, &_v0, box_size)
+  let size = mesh_bounds(tris).extents() * 2.0;
+  Ok(cmp::max(size.x, cmp::max(size.y, size.z)) / (res as f32))
+}
+
+/* Bounding box of the triangles' verts. */
+fn mesh_bounds(tris: &[Triangle]) -> math::BB3
+{
+  let mut bb = math::BB3::new(tris[0].verts[0].position, tris[0].verts[0].position);
+  for curr in tris.iter()
+  {
+    for vert in curr.verts.iter()
+    { bb.expand(&vert.position); }
+  }
+
+  bb
 }
 
-fn plane_cube_intersect(normal: &math::Vec3f, vert: &math::Vec3f, box_size: f32) -> bool
+/* Interpolates the triangle's vert colors at the point
+ * on the triangle which is closest to p. */
+fn sample_color(tri: &Triangle, p: &math::Vec3f) -> math::Vec3f
 {
-  let mut vmin: [f32, ..3] = [0.0, 0.0, 0.0];
-  let mut vmax: [f32, ..3] = [0.0, 0.0, 0.0];
-  let mut v;
+  let (u, v, w) = closest_barycentric(tri, p);
+
+  (tri.verts[0].color * u) + (tri.verts[1].color * v) + (tri.verts[2].color * w)
+}
+
+/* Barycentric coordinates of the point on the triangle closest to p.
+ * See Ericson's Real-Time Collision Detection, 5.1.5. */
+fn closest_barycentric(tri: &Triangle, p: &math::Vec3f) -> (f32, f32, f32)
+{
+  let a = tri.verts[0].position;
+  let b = tri.verts[1].position;
+  let c = tri.verts[2].position;
+
+  let ab = b - a;
+  let ac = c - a;
+
+  /* Vertex region outside of A. */
+  let ap = *p - a;
+  let d1 = ab.dot(&ap);
+  let d2 = ac.dot(&ap);
+  if d1 <= 0.0 && d2 <= 0.0
+  { return (1.0, 0.0, 0.0); }
+
+  /* Vertex region outside of B. */
+  let bp = *p - b;
+  let d3 = ab.dot(&bp);
+  let d4 = ac.dot(&bp);
+  if d3 >= 0.0 && d4 <= d3
+  { return (0.0, 1.0, 0.0); }
+
+  /* Edge region of AB. */
+  let vc = (d1 * d4) - (d3 * d2);
+  if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0
+  {
+    let v = d1 / (d1 - d3);
+    return (1.0 - v, v, 0.0);
+  }
+
+  /* Vertex region outside of C. */
+  let cp = *p - c;
+  let d5 = ab.dot(&cp);
+  let d6 = ac.dot(&cp);
+  if d6 >= 0.0 && d5 <= d6
+  { return (0.0, 0.0, 1.0); }
+
+  /* Edge region of AC. */
+  let vb = (d5 * d2) - (d1 * d6);
+  if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0
+  {
+    let w = d2 / (d2 - d6);
+    return (1.0 - w, 0.0, w);
+  }
+
+  /* Edge region of BC. */
+  let va = (d3 * d6) - (d5 * d4);
+  if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0
+  {
+    let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+    return (0.0, 1.0 - w, w);
+  }
+
+  /* Inside the face. */
+  let denom = va + vb + vc;
+  if (denom as f64).approx_eq(&0.0)
+  { return (1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0); } /* Degenerate triangle. */
+  let v = vb / denom;
+  let w = vc / denom;
+  (1.0 - v - w, v, w)
+}
+
+/* Area of the triangle that lies within the cube. The triangle
+ * is clipped against each of the cube's six planes. */
+fn coverage(tri: &Triangle, center: &math::Vec3f, half_size: f32) -> f32
+{
+  let mut poly = ~[ tri.verts[0].position, tri.verts[1].position, tri.verts[2].position ];
 
   for q in range(0u, 3u)
   {
-    v = vert[q];
-    if normal[q] > 0.0
+    poly = clip_polygon(poly, q, center[q] - half_size, true);
+    poly = clip_polygon(poly, q, center[q] + half_size, false);
+    if poly.len() < 3
+    { return 0.0; }
+  }
+
+  /* Fan out from the first point to sum the area. */
+  let mut sum = math::Vec3f::zero();
+  for i in range(1, poly.len() - 1)
+  { sum = sum + (poly[i] - poly[0]).cross(&(poly[i + 1] - poly[0])); }
+
+  sum.length() / 2.0
+}
+
+/* Sutherland-Hodgman clipping against a single axis-aligned plane.
+ * Keeps whatever is above the plane when keep_above, otherwise below. */
+pub fn clip_polygon(poly: &[math::Vec3f], axis: uint, plane: f32, keep_above: bool) -> ~[math::Vec3f]
+{
+  let mut out = vec::with_capacity(poly.len() + 1);
+  let inside = |p: &math::Vec3f|
+  { if keep_above { p[axis] >= plane } else { p[axis] <= plane } };
+
+  for i in range(0, poly.len())
+  {
+    let curr = poly[i];
+    let next = poly[(i + 1) % poly.len()];
+    let curr_in = inside(&curr);
+    let next_in = inside(&next);
+
+    if curr_in
+    { out.push(curr); }
+    if curr_in != next_in
     {
-      vmin[q] = -box_size - v;
-      vmax[q] = box_size - v;
+      let t = (plane - curr[axis]) / (next[axis] - curr[axis]);
+      out.push(curr + ((next - curr) * t));
     }
-    else
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use primitive::Triangle;
+  use super::Map;
+  use super::super::{ Conservative_Mode, Separating_6_Mode, Separating_26_Mode };
+
+  static RES: u32 = 8;
+
+  /* The fast path must claim exactly the cells the brute-force
+   * reference does, in every mode. */
+  fn check_against_reference(tris: &[Triangle])
+  {
+    for &mode in [ Conservative_Mode, Separating_6_Mode, Separating_26_Mode ].iter()
     {
-      vmin[q] = box_size - v;
-      vmax[q] = -box_size - v;
+      let fast = Map::new(tris, RES, mode);
+      let reference = Map::new_reference(tris, RES, mode);
+      assert!(fast.is_ok());
+      assert!(reference.is_ok());
+
+      let (fast, reference) = (fast.unwrap(), reference.unwrap());
+      assert!(fast.voxels.len() > 0);
+      assert!(fast.count_mismatches(&*reference).unwrap() == 0);
     }
   }
-  if normal[0] * vmin[0] + normal[1] * vmin[1] + normal[2] * vmin[2] > 0.0
-  { return false; }
-  if normal[0] * vmax[0] + normal[1] * vmax[1] + normal[2] * vmax[2] >= 0.0
-  { return true; }
 
-  false
-}
+  fn v(x: f32, y: f32, z: f32) -> math::Vec3f
+  { math::Vec3f::new(x, y, z) }
 
+  #[test]
+  fn axis_aligned_quad()
+  {
+    check_against_reference([ Triangle::new_with_position(v(0.0, 0.0, 0.0), v(4.0, 0.0, 0.0), v(4.0, 0.0, 4.0)),
+                              Triangle::new_with_position(v(0.0, 0.0, 0.0), v(4.0, 0.0, 4.0), v(0.0, 0.0, 4.0)) ]);
+  }
+
+  #[test]
+  fn diagonal_slab()
+  {
+    check_against_reference([ Triangle::new_with_position(v(0.0, 0.0, 0.0), v(4.0, 0.0, 0.0), v(4.0, 4.0, 4.0)),
+                              Triangle::new_with_position(v(0.0, 0.0, 0.0), v(4.0, 4.0, 4.0), v(0.0, 4.0, 4.0)) ]);
+  }
+
+  #[test]
+  fn sliver_triangle()
+  {
+    check_against_reference([ Triangle::new_with_position(v(0.0, 0.0, 0.0), v(6.0, 1.0, 0.5), v(6.0, 1.01, 0.52)) ]);
+  }
+}
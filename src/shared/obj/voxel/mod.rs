@@ -12,8 +12,27 @@
 pub use self::map::Map;
 pub use self::vertex::Vertex;
 pub use self::behavior::{ Visible };
+pub use self::raycast::Hit;
+pub use self::collision::Sweep;
+pub use self::occlusion::Occlusion;
+pub use self::generator::{ Arena, Arena_Params };
+pub use self::lod::{ Lod, LOD_LEVELS };
+pub use self::mode::{ Mode, Conservative_Mode, Separating_6_Mode, Separating_26_Mode };
+pub use self::compact::{ Compact, Palette_Size, Small_Palette, Large_Palette };
+pub use self::patch::{ Patch, Patch_Cell };
 
 pub mod map;
 pub mod vertex;
 pub mod behavior;
+pub mod raycast;
+pub mod collision;
+pub mod vox;
+pub mod surface;
+pub mod mode;
+pub mod lod;
+pub mod occlusion;
+pub mod generator;
+pub mod heightmap;
+pub mod compact;
+pub mod patch;
 
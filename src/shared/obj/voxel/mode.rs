@@ -0,0 +1,234 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/mode.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Voxelization modes, which decide whether
+      a triangle claims a voxel. Each has a fast
+      test and a slow, brute-force reference
+      which it can be checked against.
+*/
+
+use std::cmp;
+use math;
+use primitive::Triangle;
+use super::map::clip_polygon;
+
+pub enum Mode
+{
+  /* Every voxel the triangle touches at all. */
+  Conservative_Mode,
+  /* The thinnest surface through which no 6-connected
+   * path can pass; one voxel thick along the dominant axis. */
+  Separating_6_Mode,
+  /* A thicker surface through which not even a 26-connected
+   * (diagonal) path can pass. */
+  Separating_26_Mode,
+}
+
+impl Mode
+{
+  /* Whether the triangle claims the voxel centered at center. */
+  pub fn intersects(&self, tri: &Triangle, center: math::Vec3f, voxel_size: f32) -> bool
+  {
+    match *self
+    {
+      Conservative_Mode =>
+      {
+        let h = voxel_size / 2.0;
+        math::intersect::tri_aabb(&center, &math::Vec3f::new(h, h, h),
+                                  &tri.verts[0].position, &tri.verts[1].position, &tri.verts[2].position)
+      }
+      Separating_6_Mode => separating_intersect(tri, center, voxel_size / 2.0, false),
+      Separating_26_Mode => separating_intersect(tri, center, voxel_size / 2.0, true),
+    }
+  }
+
+  /* The same decision as intersects, made by clipping the actual
+   * geometry rather than with separating axes. Much slower. */
+  pub fn intersects_reference(&self, tri: &Triangle, center: math::Vec3f, voxel_size: f32) -> bool
+  {
+    let half_size = voxel_size / 2.0;
+    match *self
+    {
+      Conservative_Mode =>
+      {
+        let mut poly = ~[ tri.verts[0].position, tri.verts[1].position, tri.verts[2].position ];
+        for q in range(0u, 3u)
+        {
+          poly = clip_polygon(poly, q, center[q] - half_size, true);
+          poly = clip_polygon(poly, q, center[q] + half_size, false);
+        }
+        poly.len() > 0
+      }
+      Separating_6_Mode => { separating_reference(tri, center, half_size, false) }
+      Separating_26_Mode => { separating_reference(tri, center, half_size, true) }
+    }
+  }
+}
+
+/* A voxel is claimed when the triangle's plane passes through it and,
+ * in each of the three axis-aligned projections, the triangle overlaps
+ * the diamond inscribed in the voxel's square. For 6-separation, the
+ * plane must cross the voxel's center line along the dominant axis of the
+ * normal; for 26-separation, it only needs to cross the voxel. */
+fn separating_intersect(tri: &Triangle, center: math::Vec3f, half_size: f32, thick: bool) -> bool
+{
+  let plane = math::Plane::new_from_points(&tri.verts[0].position, &tri.verts[1].position,
+                                           &tri.verts[2].position);
+  let normal = plane.normal;
+
+  let reach = if thick
+  { half_size * (normal.x.abs() + normal.y.abs() + normal.z.abs()) }
+  else
+  { half_size * cmp::max(normal.x.abs(), cmp::max(normal.y.abs(), normal.z.abs())) };
+  if plane.distance_to(&center).abs() > reach
+  { return false; }
+
+  for q in range(0u, 3u)
+  {
+    let (a, b) = ((q + 1) % 3, (q + 2) % 3);
+    let points = [ [ tri.verts[0].position[a], tri.verts[0].position[b] ],
+                   [ tri.verts[1].position[a], tri.verts[1].position[b] ],
+                   [ tri.verts[2].position[a], tri.verts[2].position[b] ] ];
+    if !diamond_overlap(points, [ center[a], center[b] ], half_size)
+    { return false; }
+  }
+
+  true
+}
+
+/* 2D separating axis test between a triangle and the diamond of
+ * the given radius. The candidate axes are the diamond's two edge
+ * normals and the triangle's three edge normals. */
+fn diamond_overlap(points: [[f32, ..2], ..3], center: [f32, ..2], radius: f32) -> bool
+{
+  let overlaps = |axis: [f32, ..2], extent: f32|
+  {
+    let mut min = (points[0][0] * axis[0]) + (points[0][1] * axis[1]);
+    let mut max = min;
+    for i in range(1u, 3u)
+    {
+      let p = (points[i][0] * axis[0]) + (points[i][1] * axis[1]);
+      min = cmp::min(min, p);
+      max = cmp::max(max, p);
+    }
+    let c = (center[0] * axis[0]) + (center[1] * axis[1]);
+    !(min > c + extent || max < c - extent)
+  };
+
+  if !overlaps([ 1.0, 1.0 ], radius) || !overlaps([ 1.0, -1.0 ], radius)
+  { return false; }
+
+  for i in range(0u, 3u)
+  {
+    let next = points[(i + 1) % 3];
+    let axis = [ points[i][1] - next[1], next[0] - points[i][0] ];
+    if !overlaps(axis, radius * cmp::max(axis[0].abs(), axis[1].abs()))
+    { return false; }
+  }
+
+  true
+}
+
+/* Brute-force counterpart of separating_intersect. The plane test
+ * checks the signs of the plane's distance at the ends of the center
+ * line (or at every corner of the voxel) and the projections are
+ * clipped against the diamond's four edges. */
+fn separating_reference(tri: &Triangle, center: math::Vec3f, half_size: f32, thick: bool) -> bool
+{
+  let plane = math::Plane::new_from_points(&tri.verts[0].position, &tri.verts[1].position,
+                                           &tri.verts[2].position);
+  let normal = plane.normal;
+
+  let mut points = ~[];
+  if thick
+  {
+    for i in range(0, 8)
+    {
+      let offset = math::Vec3f::new(if i & 1 == 0 { -half_size } else { half_size },
+                                    if i & 2 == 0 { -half_size } else { half_size },
+                                    if i & 4 == 0 { -half_size } else { half_size });
+      points.push(center + offset);
+    }
+  }
+  else
+  {
+    let mut dominant = 0u;
+    for q in range(1u, 3u)
+    {
+      if normal[q].abs() > normal[dominant].abs()
+      { dominant = q; }
+    }
+    let mut offset = math::Vec3f::zero();
+    match dominant
+    {
+      0 => { offset.x = half_size; }
+      1 => { offset.y = half_size; }
+      _ => { offset.z = half_size; }
+    }
+    points.push(center - offset);
+    points.push(center + offset);
+  }
+
+  let mut below = false;
+  let mut above = false;
+  for p in points.iter()
+  {
+    let dist = plane.distance_to(p);
+    if dist <= 0.0
+    { below = true; }
+    if dist >= 0.0
+    { above = true; }
+  }
+  if !(below && above)
+  { return false; }
+
+  for q in range(0u, 3u)
+  {
+    let (a, b) = ((q + 1) % 3, (q + 2) % 3);
+    let mut poly = ~[ [ tri.verts[0].position[a], tri.verts[0].position[b] ],
+                      [ tri.verts[1].position[a], tri.verts[1].position[b] ],
+                      [ tri.verts[2].position[a], tri.verts[2].position[b] ] ];
+
+    /* Each diamond edge is |x| + |y| <= r for one pair of signs. */
+    for &(sa, sb) in [ (1.0f32, 1.0f32), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0) ].iter()
+    {
+      let limit = (sa * center[a]) + (sb * center[b]) + half_size;
+      poly = clip_half_plane(poly, [ sa, sb ], limit);
+    }
+    if poly.len() == 0
+    { return false; }
+  }
+
+  true
+}
+
+/* Sutherland-Hodgman clipping of a 2D polygon, keeping
+ * the points where dot(axis, p) <= limit. */
+fn clip_half_plane(poly: &[[f32, ..2]], axis: [f32, ..2], limit: f32) -> ~[[f32, ..2]]
+{
+  let mut out = ~[];
+  let dist = |p: &[f32, ..2]| { (p[0] * axis[0]) + (p[1] * axis[1]) - limit };
+
+  for i in range(0, poly.len())
+  {
+    let curr = poly[i];
+    let next = poly[(i + 1) % poly.len()];
+    let curr_dist = dist(&curr);
+    let next_dist = dist(&next);
+
+    if curr_dist <= 0.0
+    { out.push(curr); }
+    if (curr_dist <= 0.0) != (next_dist <= 0.0)
+    {
+      let t = curr_dist / (curr_dist - next_dist);
+      out.push([ curr[0] + ((next[0] - curr[0]) * t), curr[1] + ((next[1] - curr[1]) * t) ]);
+    }
+  }
+
+  out
+}
@@ -0,0 +1,284 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/occlusion.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Baked ambient occlusion for each corner
+      of each voxel face, from the occupancy of
+      the three cells around that corner.
+*/
+
+use std::vec;
+use math;
+use super::{ Map, Visible };
+use super::map::{ grid_in_bounds, grid_index, grow_dirty };
+
+/* Direction of each face, followed by its corners, in
+ * counter-clockwise order when viewed from outside. */
+pub static FACE_NORMALS: [[i32, ..3], ..6] =
+[
+  [ 1, 0, 0 ], [ -1, 0, 0 ],
+  [ 0, 1, 0 ], [ 0, -1, 0 ],
+  [ 0, 0, 1 ], [ 0, 0, -1 ],
+];
+pub static FACE_CORNERS: [[[f32, ..3], ..4], ..6] =
+[
+  [ [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 1.0] ],
+  [ [0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0] ],
+  [ [0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0] ],
+  [ [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0] ],
+  [ [0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0] ],
+  [ [0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0] ],
+];
+
+/* Occlusion of a fully open corner. */
+pub static OPEN_CORNER: u8 = 3;
+
+/* One term per corner, indexed by (face * 4) + corner, in the order
+ * of FACE_NORMALS and FACE_CORNERS. Each term ranges from zero (a
+ * fully occluded corner) to OPEN_CORNER. */
+#[packed]
+pub struct Occlusion
+{
+  corners: [u8, ..24],
+}
+
+impl Occlusion
+{
+  pub fn new() -> Occlusion
+  { Occlusion { corners: [OPEN_CORNER, ..24] } }
+
+  pub fn get(&self, face: uint, corner: uint) -> u8
+  { self.corners[(face * 4) + corner] }
+}
+
+impl Map
+{
+  /* Computes the occlusion of every voxel from scratch. */
+  pub fn bake_occlusion(&mut self) -> bool
+  {
+    let occlusion = match self.states
+    {
+      Some(ref states) =>
+      {
+        let mut occlusion = vec::with_capacity(self.voxels.len());
+        for voxel in self.voxels.iter()
+        {
+          let cell = [ voxel.position.x as i32, voxel.position.y as i32, voxel.position.z as i32 ];
+          occlusion.push(voxel_occlusion(self.dimensions, *states, cell));
+        }
+        occlusion
+      }
+      None => { self.error = ~"Voxel states are unavailable"; return false; }
+    };
+
+    self.occlusion = occlusion;
+    true
+  }
+
+  /* Removes the voxel in the cell, if there is one, and updates the
+   * occlusion of its neighbors. The last voxel is moved into the
+   * removed voxel's slot, to keep them contiguous, which renumbers
+   * it; the revision is bumped so that renderers, whose buffers are
+   * indexed by voxel, upload them again, and the two changed states
+   * are marked dirty. LODs are left alone; rebuild them once carving
+   * is done. */
+  pub fn carve(&mut self, cell: &math::Vec3i) -> bool
+  {
+    let dims = self.dimensions;
+    if !self.in_bounds(cell)
+    { return false; }
+
+    let states = match self.states
+    {
+      Some(ref mut states) => states,
+      None => { self.error = ~"Voxel states are unavailable"; return false; }
+    };
+
+    /* The states are borrowed, so use the free cell helpers. */
+    let index = grid_index(&dims, cell);
+    if (states[index] & Visible) == 0
+    { return false; }
+
+    let voxel = (states[index] & !Visible) as uint;
+    states[index] = 0;
+    grow_dirty(&mut self.dirty, index, index + 1);
+
+    let last = self.voxels.len() - 1;
+    if voxel != last
+    {
+      let moved = self.voxels[last].position;
+      let moved_index = grid_index(&dims, &moved.to_i());
+      states[moved_index] = voxel as u32;
+      states[moved_index] |= Visible;
+      grow_dirty(&mut self.dirty, moved_index, moved_index + 1);
+    }
+    self.voxels.swap_remove(voxel);
+    self.occlusion.swap_remove(voxel);
+    self.revision += 1;
+
+    /* Only the surrounding voxels could have seen this one. */
+    for z in range(cell.z - 1, cell.z + 2)
+    { for y in range(cell.y - 1, cell.y + 2)
+      { for x in range(cell.x - 1, cell.x + 2)
+        {
+          let neighbor = math::Vec3i::new(x, y, z);
+          if !grid_in_bounds(&dims, &neighbor)
+          { continue; }
+
+          let state = states[grid_index(&dims, &neighbor)];
+          if (state & Visible) != 0
+          { self.occlusion[state & !Visible] = voxel_occlusion(dims, *states, [x, y, z]); }
+        }
+      }
+    }
+
+    true
+  }
+}
+
+/* The classic three-neighbor corner term: each corner looks at the
+ * two cells beside it and the one diagonal to it, all in the layer
+ * the face looks out onto. Two solid sides fully occlude the corner,
+ * regardless of the diagonal. */
+fn voxel_occlusion(dims: math::Vec3i, states: &[u32], cell: [i32, ..3]) -> Occlusion
+{
+  let solid = |p: [i32, ..3]|
+  {
+    let p = math::Vec3i::new(p[0], p[1], p[2]);
+    grid_in_bounds(&dims, &p) && (states[grid_index(&dims, &p)] & Visible) != 0
+  };
+
+  let mut occlusion = Occlusion::new();
+  for face in range(0u, 6u)
+  {
+    let axis = if FACE_NORMALS[face][0] != 0 { 0 } else if FACE_NORMALS[face][1] != 0 { 1 } else { 2 };
+    let (ta, tb) = ((axis + 1) % 3, (axis + 2) % 3);
+
+    let mut layer = cell;
+    layer[axis] += FACE_NORMALS[face][axis];
+
+    for corner in range(0u, 4u)
+    {
+      /* Which way the corner lies along each of the face's axes. */
+      let da = if FACE_CORNERS[face][corner][ta] > 0.5 { 1 } else { -1 };
+      let db = if FACE_CORNERS[face][corner][tb] > 0.5 { 1 } else { -1 };
+
+      let mut side_a = layer;
+      side_a[ta] += da;
+      let mut side_b = layer;
+      side_b[tb] += db;
+      let mut diagonal = side_a;
+      diagonal[tb] += db;
+
+      let (a, b, c) = (solid(side_a), solid(side_b), solid(diagonal));
+      occlusion.corners[(face * 4) + corner] = if a && b
+      { 0 }
+      else
+      { OPEN_CORNER - (a as u8) - (b as u8) - (c as u8) };
+    }
+  }
+
+  occlusion
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use super::{ Occlusion, OPEN_CORNER };
+  use super::super::{ Map, Vertex, Visible };
+
+  /* The +Y face, and its corner nearest -X and -Z. */
+  static TOP: uint = 2;
+  static TOP_NEAR: uint = 0;
+
+  fn new_grid(cells: &[(i32, i32, i32)]) -> @mut Map
+  {
+    let voxels = do cells.map |&(x, y, z)|
+    { Vertex::new(math::Vec3f::new(x as f32, y as f32, z as f32), math::Vec3f::new(1.0, 1.0, 1.0)) };
+    Map::new_with_voxels(math::Vec3i::new(3, 3, 3), 1.0, math::Vec3f::zero(), voxels).unwrap()
+  }
+
+  fn occlusion_at(map: &Map, x: i32, y: i32, z: i32) -> Occlusion
+  {
+    let state = map.states.get_ref()[map.cell_index(&math::Vec3i::new(x, y, z))];
+    assert!((state & Visible) != 0);
+    map.occlusion[state & !Visible]
+  }
+
+  /* The center voxel's top face, with some of the layer above it. */
+  fn top_near(above: &[(i32, i32, i32)]) -> u8
+  {
+    let mut cells = ~[ (1, 1, 1) ];
+    cells.push_all(above);
+    let map = new_grid(cells);
+    occlusion_at(&*map, 1, 1, 1).get(TOP, TOP_NEAR)
+  }
+
+  #[test]
+  fn open_corners()
+  {
+    let map = new_grid([ (1, 1, 1) ]);
+    let occlusion = occlusion_at(&*map, 1, 1, 1);
+    for i in range(0u, 24u)
+    { assert!(occlusion.corners[i] == OPEN_CORNER); }
+  }
+
+  #[test]
+  fn corner_values()
+  {
+    /* Diagonal, then either side, then a side and the diagonal. */
+    assert!(top_near([ (0, 2, 0) ]) == OPEN_CORNER - 1);
+    assert!(top_near([ (1, 2, 0) ]) == OPEN_CORNER - 1);
+    assert!(top_near([ (0, 2, 1) ]) == OPEN_CORNER - 1);
+    assert!(top_near([ (1, 2, 0), (0, 2, 0) ]) == OPEN_CORNER - 2);
+
+    /* Only the layer the face looks onto matters. */
+    assert!(top_near([ (0, 1, 0), (1, 1, 0), (0, 1, 1) ]) == OPEN_CORNER);
+  }
+
+  #[test]
+  fn two_sides_fully_occlude()
+  {
+    assert!(top_near([ (1, 2, 0), (0, 2, 1) ]) == 0);
+    assert!(top_near([ (1, 2, 0), (0, 2, 1), (0, 2, 0) ]) == 0);
+  }
+
+  #[test]
+  fn carve_matches_bake()
+  {
+    let mut cells = ~[];
+    for z in range(0, 3)
+    { for y in range(0, 3)
+      { for x in range(0, 3)
+        { cells.push((x, y, z)); }
+      }
+    }
+    let map = new_grid(cells);
+
+    /* The center, a corner, and an edge; the last voxel is moved
+     * into each removed slot, so this covers renumbering too. */
+    for &(x, y, z) in [ (1, 1, 1), (0, 0, 0), (2, 1, 0) ].iter()
+    {
+      let revision = map.revision;
+      assert!(map.carve(&math::Vec3i::new(x, y, z)));
+      assert!(map.revision != revision);
+
+      let carved = map.occlusion.map(|o| *o);
+      assert!(map.bake_occlusion());
+      assert!(carved.len() == map.occlusion.len());
+      for i in range(0, carved.len())
+      {
+        for c in range(0u, 24u)
+        { assert!(carved[i].corners[c] == map.occlusion[i].corners[c]); }
+      }
+    }
+
+    /* Carving an empty cell does nothing. */
+    assert!(!map.carve(&math::Vec3i::new(1, 1, 1)));
+  }
+}
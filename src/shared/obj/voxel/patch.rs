@@ -0,0 +1,379 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/patch.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Differences between two voxel maps of the
+      same grid, as cells removed and cells added
+      (or recolored). Patches serialize to a small
+      binary form, so they can be sent over the
+      wire or saved as the destruction done to a
+      pristine map.
+*/
+
+use std::vec;
+use std::rt::io::{ Reader, Writer };
+use std::rt::io::File;
+use math;
+use bytes::{ push_i32, Buffer };
+use super::{ Map, Vertex, Visible };
+use super::map::{ grid_cell, grow_dirty };
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+static MAGIC: &'static str = "VXPT";
+static VERSION: i32 = 1;
+/* The only flags an added cell may have. */
+static ADDED_FLAGS: u8 = (Visible >> 24) as u8;
+
+/* A cell which is solid after the patch. */
+pub struct Patch_Cell
+{
+  /* Index into Map::states. */
+  cell: u32,
+  color: [u8, ..3],
+  /* The high byte of the cell's state. Only Visible is defined,
+   * so that's all this can be; the rest of the state is the voxel's
+   * index, and a stray bit there would point it past the voxels. */
+  flags: u8,
+}
+
+pub struct Patch
+{
+  /* The grid both maps share. */
+  dimensions: math::Vec3i,
+  /* Cells, by index, which are empty after the patch. */
+  removed: ~[u32],
+  added: ~[Patch_Cell],
+}
+
+impl Patch
+{
+  /* An empty patch means the two maps agree. */
+  pub fn is_empty(&self) -> bool
+  { self.removed.len() == 0 && self.added.len() == 0 }
+
+  /* Layout, all little-endian:
+   *   magic, version, dimensions (3 x i32),
+   *   removed count, removed cells (i32 each),
+   *   added count, added cells (i32 cell, r, g, b, flags each) */
+  pub fn to_bytes(&self) -> ~[u8]
+  {
+    let mut out = vec::with_capacity(28 + (self.removed.len() * 4) + (self.added.len() * 8));
+    out.push_all(MAGIC.as_bytes());
+    push_i32(&mut out, VERSION);
+    push_i32(&mut out, self.dimensions.x);
+    push_i32(&mut out, self.dimensions.y);
+    push_i32(&mut out, self.dimensions.z);
+
+    push_i32(&mut out, self.removed.len() as i32);
+    for cell in self.removed.iter()
+    { push_i32(&mut out, *cell as i32); }
+
+    push_i32(&mut out, self.added.len() as i32);
+    for added in self.added.iter()
+    {
+      push_i32(&mut out, added.cell as i32);
+      out.push_all(added.color);
+      out.push(added.flags);
+    }
+
+    out
+  }
+
+  pub fn from_bytes(data: ~[u8]) -> Result<Patch, ~str>
+  {
+    let mut buf = Buffer::new(data);
+    if buf.read_id() != Some(MAGIC.to_owned())
+    { return Err(~"Invalid voxel patch magic"); }
+    match buf.read_i32()
+    {
+      Some(VERSION) => { }
+      Some(version) => { return Err(format!("Unsupported voxel patch version {}", version)); }
+      None => { return Err(~"Truncated voxel patch header"); }
+    }
+
+    let x = buf.read_i32();
+    let y = buf.read_i32();
+    let z = buf.read_i32();
+    if x.is_none() || y.is_none() || z.is_none()
+    { return Err(~"Truncated voxel patch header"); }
+    let dimensions = math::Vec3i::new(x.unwrap(), y.unwrap(), z.unwrap());
+    if dimensions.x < 1 || dimensions.y < 1 || dimensions.z < 1
+    { return Err(format!("Invalid voxel patch dimensions {}", dimensions.to_str())); }
+    let cell_count = (dimensions.x * dimensions.y * dimensions.z) as u32;
+
+    let count = buf.read_i32().unwrap_or(-1);
+    if count < 0 || buf.pos + ((count as uint) * 4) > buf.data.len()
+    { return Err(~"Truncated voxel patch removals"); }
+    let mut removed = vec::with_capacity(count as uint);
+    for _ in range(0, count)
+    {
+      let cell = buf.read_i32().unwrap() as u32;
+      if cell >= cell_count
+      { return Err(format!("Removed cell {} is outside of the grid", cell)); }
+      removed.push(cell);
+    }
+
+    let count = buf.read_i32().unwrap_or(-1);
+    if count < 0 || buf.pos + ((count as uint) * 8) > buf.data.len()
+    { return Err(~"Truncated voxel patch additions"); }
+    let mut added = vec::with_capacity(count as uint);
+    for _ in range(0, count)
+    {
+      let cell = buf.read_i32().unwrap() as u32;
+      if cell >= cell_count
+      { return Err(format!("Added cell {} is outside of the grid", cell)); }
+
+      let bytes = buf.data.slice(buf.pos, buf.pos + 4);
+      if bytes[3] != ADDED_FLAGS
+      { return Err(format!("Added cell {} has invalid flags {}", cell, bytes[3])); }
+      added.push(Patch_Cell
+      {
+        cell: cell,
+        color: [ bytes[0], bytes[1], bytes[2] ],
+        flags: bytes[3],
+      });
+      buf.pos += 4;
+    }
+
+    Ok(Patch
+    {
+      dimensions: dimensions,
+      removed: removed,
+      added: added,
+    })
+  }
+
+  pub fn save(&self, file: &str) -> Result<(), ~str>
+  {
+    let fio = File::create(&Path::new(file));
+    if fio.is_none()
+    { return Err(format!("Failed to write file: {}", file)); }
+    fio.unwrap().write(self.to_bytes());
+
+    log_debug!("Saved voxel patch of {} removed and {} added cells to {}",
+               self.removed.len(), self.added.len(), file);
+    Ok(())
+  }
+
+  pub fn load(file: &str) -> Result<Patch, ~str>
+  {
+    let fio = File::open(&Path::new(file));
+    if fio.is_none()
+    { return Err(format!("Failed to read file: {}", file)); }
+
+    Patch::from_bytes(fio.unwrap().read_to_end())
+  }
+}
+
+impl Map
+{
+  /* The patch which turns this map into other. Colors are compared
+   * at eight bits per channel, which is all a patch carries. */
+  pub fn diff(&self, other: &Map) -> Result<Patch, ~str>
+  {
+    if self.dimensions != other.dimensions
+    { return Err(~"Maps have different dimensions"); }
+
+    let (states, other_states) = match (&self.states, &other.states)
+    {
+      (&Some(ref states), &Some(ref other_states)) => (states, other_states),
+      _ => { return Err(~"Voxel states are unavailable"); }
+    };
+
+    let mut removed = ~[];
+    let mut added = ~[];
+    for i in range(0, states.len())
+    {
+      let (a, b) = (states[i], other_states[i]);
+      if (b & Visible) == 0
+      {
+        if (a & Visible) != 0
+        { removed.push(i as u32); }
+        continue;
+      }
+
+      let color = pack_color(&other.voxels[b & !Visible].color);
+      if (a & Visible) != 0
+      {
+        let prev = pack_color(&self.voxels[a & !Visible].color);
+        if prev[0] == color[0] && prev[1] == color[1] && prev[2] == color[2]
+        { continue; }
+      }
+
+      added.push(Patch_Cell { cell: i as u32, color: color, flags: ADDED_FLAGS });
+    }
+
+    Ok(Patch
+    {
+      dimensions: self.dimensions,
+      removed: removed,
+      added: added,
+    })
+  }
+
+  /* Removes and adds the patch's cells, then rebuilds occlusion and
+   * drops any LODs. Voxels are repacked, so any held voxel indices are stale;
+   * the revision is bumped, and every state marked dirty, for
+   * renderers to upload them again. */
+  pub fn apply_patch(&mut self, patch: &Patch) -> bool
+  {
+    if self.dimensions != patch.dimensions
+    { self.error = ~"Patch is for a different grid"; return false; }
+
+    let dims = self.dimensions;
+    let voxels = match self.states
+    {
+      Some(ref mut states) =>
+      {
+        for cell in patch.removed.iter()
+        { states[*cell] = 0; }
+        for added in patch.added.iter()
+        { states[added.cell] = 0; }
+
+        /* Keep the untouched voxels, repacked in grid order. */
+        let mut voxels = vec::with_capacity(self.voxels.len() + patch.added.len());
+        for i in range(0, states.len())
+        {
+          if (states[i] & Visible) == 0
+          { continue; }
+
+          voxels.push(self.voxels[states[i] & !Visible]);
+          states[i] = (voxels.len() - 1) as u32 | Visible;
+        }
+
+        for added in patch.added.iter()
+        {
+          let cell = grid_cell(&dims, added.cell as uint);
+          states[added.cell] = voxels.len() as u32 | Visible;
+          voxels.push(Vertex
+          {
+            position: cell.to_f(),
+            color: math::Vec3f::new(added.color[0] as f32 / 255.0,
+                                    added.color[1] as f32 / 255.0,
+                                    added.color[2] as f32 / 255.0),
+          });
+        }
+        /* Everything visible was renumbered. */
+        grow_dirty(&mut self.dirty, 0, states.len());
+        voxels
+      }
+      None => { self.error = ~"Voxel states are unavailable"; return false; }
+    };
+
+    log_debug!("Patched voxel map: {} removed, {} added", patch.removed.len(), patch.added.len());
+    self.voxels = voxels;
+    self.revision += 1;
+    self.lods = ~[]; /* Stale; they're rebuilt on request. */
+    self.bake_occlusion()
+  }
+}
+
+fn pack_color(color: &math::Vec3f) -> [u8, ..3]
+{
+  [ (color.x.clamp(&0.0, &1.0) * 255.0).round() as u8,
+    (color.y.clamp(&0.0, &1.0) * 255.0).round() as u8,
+    (color.z.clamp(&0.0, &1.0) * 255.0).round() as u8 ]
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use super::{ Patch, Patch_Cell, ADDED_FLAGS };
+  use super::super::{ Map, Vertex, Visible };
+
+  fn new_map(cells: &[((i32, i32, i32), (f32, f32, f32))]) -> @mut Map
+  {
+    let voxels = do cells.map |&((x, y, z), (r, g, b))|
+    { Vertex::new(math::Vec3f::new(x as f32, y as f32, z as f32), math::Vec3f::new(r, g, b)) };
+    Map::new_with_voxels(math::Vec3i::new(4, 4, 4), 1.0, math::Vec3f::zero(), voxels).unwrap()
+  }
+
+  fn sample() -> Patch
+  {
+    Patch
+    {
+      dimensions: math::Vec3i::new(4, 4, 4),
+      removed: ~[ 0, 17, 63 ],
+      added: ~[ Patch_Cell { cell: 5, color: [ 1, 2, 3 ], flags: ADDED_FLAGS },
+                Patch_Cell { cell: 40, color: [ 255, 128, 0 ], flags: ADDED_FLAGS } ],
+    }
+  }
+
+  #[test]
+  fn bytes_round_trip()
+  {
+    let patch = sample();
+    let copy = Patch::from_bytes(patch.to_bytes()).unwrap();
+    assert!(copy.dimensions == patch.dimensions);
+    assert!(copy.removed == patch.removed);
+    assert!(copy.added.len() == patch.added.len());
+    for (a, b) in copy.added.iter().zip(patch.added.iter())
+    {
+      assert!(a.cell == b.cell);
+      assert!(a.color[0] == b.color[0] && a.color[1] == b.color[1] && a.color[2] == b.color[2]);
+      assert!(a.flags == b.flags);
+    }
+  }
+
+  #[test]
+  fn rejects_bad_input()
+  {
+    /* Every truncation, including an empty buffer. */
+    let bytes = sample().to_bytes();
+    for len in range(0, bytes.len())
+    { assert!(Patch::from_bytes(bytes.slice(0, len).to_owned()).is_err()); }
+
+    /* Flags other than Visible; the added cells' flags are last. */
+    let mut bad = bytes.clone();
+    let last = bad.len() - 1;
+    bad[last] |= 0x01;
+    assert!(Patch::from_bytes(bad).is_err());
+
+    /* A cell past the end of the grid; the first removed cell. */
+    let mut bad = bytes.clone();
+    bad[24] = 64;
+    assert!(Patch::from_bytes(bad).is_err());
+  }
+
+  #[test]
+  fn diff_then_apply()
+  {
+    let map = new_map([ ((0, 0, 0), (1.0, 0.0, 0.0)),
+                        ((1, 0, 0), (0.0, 1.0, 0.0)),
+                        ((2, 0, 0), (0.0, 0.0, 1.0)),
+                        ((3, 3, 3), (1.0, 1.0, 1.0)) ]);
+    /* (1, 0, 0) is removed, (2, 0, 0) recolored, and (0, 2, 1) added. */
+    let target = new_map([ ((0, 0, 0), (1.0, 0.0, 0.0)),
+                           ((2, 0, 0), (1.0, 1.0, 0.0)),
+                           ((3, 3, 3), (1.0, 1.0, 1.0)),
+                           ((0, 2, 1), (0.0, 1.0, 1.0)) ]);
+
+    let patch = map.diff(&*target).unwrap();
+    assert!(patch.removed.len() == 1);
+    assert!(patch.added.len() == 2);
+
+    /* Through bytes, as it would go over the wire. */
+    let patch = Patch::from_bytes(patch.to_bytes()).unwrap();
+    let revision = map.revision;
+    assert!(map.apply_patch(&patch));
+    assert!(map.revision != revision);
+    assert!(map.diff(&*target).unwrap().is_empty());
+    assert!(map.count_mismatches(&*target).unwrap() == 0);
+
+    let states = map.states.get_ref();
+    for i in range(0, states.len())
+    {
+      if (states[i] & Visible) != 0
+      { assert!(((states[i] & !Visible) as uint) < map.voxels.len()); }
+    }
+  }
+}
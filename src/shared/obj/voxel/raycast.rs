@@ -0,0 +1,237 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/raycast.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Ray casting against the voxel grid
+      using Amanatides and Woo's 3D DDA.
+*/
+
+use std::cmp;
+use math;
+use super::{ Map, Visible };
+
+pub struct Hit
+{
+  /* Grid coordinates of the voxel that was hit. */
+  cell: math::Vec3i,
+  /* Normal of the face through which the ray entered the voxel.
+   * This is zero if the ray started inside of the voxel. */
+  normal: math::Vec3i,
+  /* World-space distance from the origin to the hit. */
+  distance: f32,
+  /* Index of the voxel in Map::voxels. */
+  voxel: u32,
+  /* The only material we currently have is color. */
+  material: math::Vec3f,
+}
+
+impl Map
+{
+  /* Walks the grid, one voxel at a time, along the ray and
+   * reports the first visible voxel within max_dist. Without
+   * states, there's nothing to test against, so that's an error
+   * rather than a miss. */
+  pub fn raycast(&self, ray: &math::Ray, max_dist: f32) -> Result<Option<Hit>, ~str>
+  {
+    let states = match self.states
+    {
+      Some(ref states) => states,
+      None => { return Err(~"Voxel states are unavailable"); }
+    };
+
+    let origin = ray.origin;
+    let dir = math::Vec3f::new_normalized(&ray.dir);
+    if (dir.length() as f64).approx_eq(&0.0) || max_dist <= 0.0
+    { return Ok(None); }
+
+    /* Work in grid space, where each voxel is one unit across. */
+    let start = self.world_to_grid(&origin);
+    let max_t = max_dist / self.voxel_size;
+
+    /* Clip the ray against the grid's bounds. */
+    let mut t_enter = 0.0f32;
+    let mut t_exit = max_t;
+    let mut enter_axis = -1;
+    for q in range(0u, 3u)
+    {
+      let size = self.dimensions[q] as f32;
+      if (dir[q] as f64).approx_eq(&0.0)
+      {
+        if start[q] < 0.0 || start[q] > size
+        { return Ok(None); }
+        continue;
+      }
+
+      let t0 = (0.0 - start[q]) / dir[q];
+      let t1 = (size - start[q]) / dir[q];
+      let near = cmp::min(t0, t1);
+      let far = cmp::max(t0, t1);
+      if near > t_enter
+      { t_enter = near; enter_axis = q as int; }
+      t_exit = cmp::min(t_exit, far);
+    }
+    if t_enter > t_exit
+    { return Ok(None); }
+
+    /* Find the first voxel along the ray. */
+    let mut cell = [0i32, ..3];
+    let mut step = [0i32, ..3];
+    let mut t_max = [0.0f32, ..3];
+    let mut t_delta = [0.0f32, ..3];
+    let mut normal = [0i32, ..3];
+    for q in range(0u, 3u)
+    {
+      let p = start[q] + (dir[q] * t_enter);
+      cell[q] = (p.floor() as i32).clamp(&0, &(self.dimensions[q] - 1));
+
+      if dir[q] > 0.0
+      {
+        step[q] = 1;
+        t_max[q] = t_enter + (((cell[q] + 1) as f32 - p) / dir[q]);
+        t_delta[q] = 1.0 / dir[q];
+      }
+      else if dir[q] < 0.0
+      {
+        step[q] = -1;
+        t_max[q] = t_enter + ((cell[q] as f32 - p) / dir[q]);
+        t_delta[q] = -1.0 / dir[q];
+      }
+      else
+      {
+        /* Never crosses a boundary on this axis. */
+        step[q] = 0;
+        t_max[q] = max_t + 1.0;
+        t_delta[q] = 0.0;
+      }
+    }
+    if enter_axis >= 0
+    { normal[enter_axis] = -step[enter_axis]; }
+
+    let mut t = t_enter;
+    loop
+    {
+      let curr = math::Vec3i::new(cell[0], cell[1], cell[2]);
+      let state = states[self.cell_index(&curr)];
+      if (state & Visible) != 0
+      {
+        let voxel = state & !Visible;
+        return Ok(Some(Hit
+        {
+          cell: curr,
+          normal: math::Vec3i::new(normal[0], normal[1], normal[2]),
+          distance: t * self.voxel_size,
+          voxel: voxel,
+          material: self.voxels[voxel].color,
+        }));
+      }
+
+      /* Step across whichever voxel boundary is closest. */
+      let axis = if t_max[0] < t_max[1]
+      { if t_max[0] < t_max[2] { 0 } else { 2 } }
+      else
+      { if t_max[1] < t_max[2] { 1 } else { 2 } };
+
+      t = t_max[axis];
+      if t > t_exit
+      { return Ok(None); }
+
+      cell[axis] += step[axis];
+      if !self.in_bounds(&math::Vec3i::new(cell[0], cell[1], cell[2]))
+      { return Ok(None); }
+
+      normal = [0, 0, 0];
+      normal[axis] = -step[axis];
+      t_max[axis] += t_delta[axis];
+    }
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use math;
+  use super::super::{ Map, Vertex };
+
+  /* A 4x4x4 grid, one unit per voxel, with the given cells solid. */
+  fn new_grid(cells: &[(i32, i32, i32)], voxel_size: f32, origin: math::Vec3f) -> @mut Map
+  {
+    let voxels = do cells.map |&(x, y, z)|
+    { Vertex::new(math::Vec3f::new(x as f32, y as f32, z as f32), math::Vec3f::new(1.0, 0.5, 0.25)) };
+    Map::new_with_voxels(math::Vec3i::new(4, 4, 4), voxel_size, origin, voxels).unwrap()
+  }
+
+  fn near(a: f32, b: f32) -> bool
+  { (a - b).abs() < 0.0001 }
+
+  #[test]
+  fn axis_aligned_hit()
+  {
+    let map = new_grid([ (2, 1, 1) ], 1.0, math::Vec3f::zero());
+    let hit = map.raycast(&math::Ray::new(math::Vec3f::new(0.5, 1.5, 1.5), math::Vec3f::new(1.0, 0.0, 0.0)), 10.0).unwrap().unwrap();
+    assert!(hit.cell == math::Vec3i::new(2, 1, 1));
+    assert!(hit.normal == math::Vec3i::new(-1, 0, 0));
+    assert!(near(hit.distance, 1.5));
+    assert!(hit.voxel == 0);
+    assert!(hit.material == math::Vec3f::new(1.0, 0.5, 0.25));
+  }
+
+  #[test]
+  fn diagonal_through_edge()
+  {
+    /* The ray passes exactly through the edge shared by (0, 1, 0),
+     * (1, 0, 0), and (1, 1, 0), so whichever way the tie breaks, it
+     * reaches (1, 1, 0) where it crosses that edge. */
+    let map = new_grid([ (1, 1, 0) ], 1.0, math::Vec3f::zero());
+    let hit = map.raycast(&math::Ray::new(math::Vec3f::new(0.5, 0.5, 0.5), math::Vec3f::new(1.0, 1.0, 0.0)), 10.0).unwrap().unwrap();
+    assert!(hit.cell == math::Vec3i::new(1, 1, 0));
+    assert!(near(hit.distance, (0.5f32).sqrt()));
+  }
+
+  #[test]
+  fn origin_inside_solid()
+  {
+    let map = new_grid([ (1, 1, 1) ], 1.0, math::Vec3f::zero());
+    let hit = map.raycast(&math::Ray::new(math::Vec3f::new(1.5, 1.5, 1.5), math::Vec3f::new(0.0, 1.0, 0.0)), 10.0).unwrap().unwrap();
+    assert!(hit.cell == math::Vec3i::new(1, 1, 1));
+    assert!(hit.normal == math::Vec3i::zero());
+    assert!(near(hit.distance, 0.0));
+  }
+
+  #[test]
+  fn miss_past_max_dist()
+  {
+    /* The voxel's near face is 2.5 away. */
+    let map = new_grid([ (3, 1, 1) ], 1.0, math::Vec3f::zero());
+    let ray = math::Ray::new(math::Vec3f::new(0.5, 1.5, 1.5), math::Vec3f::new(1.0, 0.0, 0.0));
+    assert!(map.raycast(&ray, 2.0).unwrap().is_none());
+    assert!(near(map.raycast(&ray, 3.0).unwrap().unwrap().distance, 2.5));
+  }
+
+  #[test]
+  fn origin_outside_grid()
+  {
+    /* Two world units per voxel, with the grid starting at x = -4; the
+     * ray starts 2.5 voxels (5 units) before the grid. */
+    let map = new_grid([ (0, 1, 1) ], 2.0, math::Vec3f::new(-4.0, 0.0, 0.0));
+    let hit = map.raycast(&math::Ray::new(math::Vec3f::new(-9.0, 3.0, 3.0), math::Vec3f::new(1.0, 0.0, 0.0)), 100.0).unwrap().unwrap();
+    assert!(hit.cell == math::Vec3i::new(0, 1, 1));
+    assert!(hit.normal == math::Vec3i::new(-1, 0, 0));
+    assert!(near(hit.distance, 5.0));
+
+    /* Pointing away from, or passing beside, the grid. */
+    assert!(map.raycast(&math::Ray::new(math::Vec3f::new(-9.0, 3.0, 3.0), math::Vec3f::new(-1.0, 0.0, 0.0)), 100.0).unwrap().is_none());
+    assert!(map.raycast(&math::Ray::new(math::Vec3f::new(-9.0, 30.0, 3.0), math::Vec3f::new(1.0, 0.0, 0.0)), 100.0).unwrap().is_none());
+  }
+
+  #[test]
+  fn states_unavailable()
+  {
+    let map = new_grid([ (1, 1, 1) ], 1.0, math::Vec3f::zero());
+    map.states = None;
+    assert!(map.raycast(&math::Ray::new(math::Vec3f::zero(), math::Vec3f::new(1.0, 1.0, 1.0)), 10.0).is_err());
+  }
+}
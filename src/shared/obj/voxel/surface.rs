@@ -0,0 +1,71 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/surface.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Extraction of the exposed faces of the
+      voxel map as a triangle soup, mainly so
+      the voxelizer's output can be exported
+      and inspected outside the game.
+*/
+
+use math;
+use primitive::{ Triangle, Vertex_PC };
+use mesh::Mesh;
+use super::{ Map, Visible };
+use super::occlusion::{ FACE_NORMALS, FACE_CORNERS };
+
+impl Map
+{
+  /* Two world-space triangles for every voxel face that isn't
+   * covered by a neighboring voxel. Colors are in [0, 255]. */
+  pub fn surface_tris(&self) -> Result<~[Triangle], ~str>
+  {
+    let states = match self.states
+    {
+      Some(ref states) => states,
+      None => { return Err(~"Voxel states are unavailable"); }
+    };
+
+    let mut tris = ~[];
+    for voxel in self.voxels.iter()
+    {
+      let cell = voxel.position.to_i();
+      let (min, _) = self.cell_bounds(&cell);
+      let color = voxel.color * 255.0;
+
+      for face in range(0u, 6u)
+      {
+        let normal = FACE_NORMALS[face];
+        let neighbor = cell + math::Vec3i::new(normal[0], normal[1], normal[2]);
+        let exposed = !self.in_bounds(&neighbor) ||
+                      (states[self.cell_index(&neighbor)] & Visible) == 0;
+        if !exposed
+        { continue; }
+
+        let corner = |i: uint|
+        {
+          let c = FACE_CORNERS[face][i];
+          Vertex_PC::new(min + (math::Vec3f::new(c[0], c[1], c[2]) * self.voxel_size), color)
+        };
+        tris.push(Triangle::new(corner(0), corner(1), corner(2)));
+        tris.push(Triangle::new(corner(0), corner(2), corner(3)));
+      }
+    }
+
+    Ok(tris)
+  }
+
+  /* Writes the surface to an OBJ or PLY file, by extension. */
+  pub fn export_mesh(&self, file: &str) -> Result<(), ~str>
+  {
+    let tris = self.surface_tris();
+    if tris.is_err()
+    { return Err(tris.unwrap_err()); }
+
+    Mesh::export(tris.unwrap(), file)
+  }
+}
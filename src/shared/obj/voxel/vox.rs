@@ -0,0 +1,545 @@
+/*
+    Copyright 2013 Jesse 'Jeaye' Wilkerson
+    See licensing in LICENSE file, or at:
+        http://www.opensource.org/licenses/BSD-3-Clause
+
+    File: shared/obj/voxel/vox.rs
+    Author: Jesse 'Jeaye' Wilkerson
+    Description:
+      Import and export of voxel maps using
+      MagicaVoxel's .vox format. MagicaVoxel
+      is Z up, so the Y and Z axes are swapped
+      (and Z flipped) on the way in and out.
+*/
+
+use std::{ vec, cmp };
+use std::rt::io::{ Reader, Writer };
+use std::rt::io::File;
+use math;
+use bytes::{ push_i32, push_f32, push_string, Buffer };
+use super::{ Map, Vertex, Visible };
+use super::compact;
+use log::Log;
+
+#[macro_escape]
+#[path = "../../log/macros.rs"]
+mod macros;
+
+static VERSION: i32 = 150;
+/* Models can't be larger than this along any axis. */
+static MODEL_SIZE: i32 = 256;
+/* Palette indices are one-based; zero is empty space. */
+static PALETTE_SIZE: uint = 255;
+
+/* A single voxel, in MagicaVoxel's axes, and its palette index. */
+struct Vox_Voxel
+{
+  position: math::Vec3i,
+  color: u8,
+}
+
+impl Map
+{
+  /* Writes the map out as a .vox, split into as many models
+   * as it takes to fit it. Colors are quantized to a palette. */
+  pub fn export_vox(&self, file: &str) -> Result<(), ~str>
+  {
+    let states = match self.states
+    {
+      Some(ref states) => states,
+      None => { return Err(~"Voxel states are unavailable"); }
+    };
+
+    let (palette, indices) = compact::quantize(self.voxels, PALETTE_SIZE, 5);
+
+    /* Size, in voxels and models, of the MagicaVoxel world. */
+    let size = math::Vec3i::new(self.dimensions.x, self.dimensions.z, self.dimensions.y);
+    let models = math::Vec3i::new((size.x + MODEL_SIZE - 1) / MODEL_SIZE,
+                                  (size.y + MODEL_SIZE - 1) / MODEL_SIZE,
+                                  (size.z + MODEL_SIZE - 1) / MODEL_SIZE);
+    let mut tiles = vec::from_fn((models.x * models.y * models.z) as uint, |_| ~[]);
+
+    /* Bin every voxel into the model that contains it. */
+    for z in range(0, self.dimensions.z)
+    {
+      for y in range(0, self.dimensions.y)
+      {
+        for x in range(0, self.dimensions.x)
+        {
+          let state = states[self.cell_index(&math::Vec3i::new(x, y, z))];
+          if (state & Visible) == 0
+          { continue; }
+
+          let pos = math::Vec3i::new(x, self.dimensions.z - 1 - z, y);
+          let tile = ((pos.z / MODEL_SIZE) * models.x * models.y) +
+                     ((pos.y / MODEL_SIZE) * models.x) + (pos.x / MODEL_SIZE);
+          tiles[tile].push(Vox_Voxel
+          {
+            position: math::Vec3i::new(pos.x % MODEL_SIZE, pos.y % MODEL_SIZE, pos.z % MODEL_SIZE),
+            color: (indices[state & !Visible] + 1) as u8,
+          });
+        }
+      }
+    }
+
+    let mut body = ~[];
+    let mut translations = ~[];
+    for tz in range(0, models.z)
+    {
+      for ty in range(0, models.y)
+      {
+        for tx in range(0, models.x)
+        {
+          let tile = &tiles[(tz * models.x * models.y) + (ty * models.x) + tx];
+          if tile.len() == 0
+          { continue; }
+
+          let start = math::Vec3i::new(tx * MODEL_SIZE, ty * MODEL_SIZE, tz * MODEL_SIZE);
+          let model_size = math::Vec3i::new(cmp::min(MODEL_SIZE, size.x - start.x),
+                                            cmp::min(MODEL_SIZE, size.y - start.y),
+                                            cmp::min(MODEL_SIZE, size.z - start.z));
+
+          let mut content = ~[];
+          push_i32(&mut content, model_size.x);
+          push_i32(&mut content, model_size.y);
+          push_i32(&mut content, model_size.z);
+          push_chunk(&mut body, "SIZE", content, []);
+
+          let mut content = ~[];
+          push_i32(&mut content, tile.len() as i32);
+          for vox in tile.iter()
+          {
+            content.push(vox.position.x as u8);
+            content.push(vox.position.y as u8);
+            content.push(vox.position.z as u8);
+            content.push(vox.color);
+          }
+          push_chunk(&mut body, "XYZI", content, []);
+
+          /* MagicaVoxel positions models by their centers. */
+          translations.push(math::Vec3i::new(start.x + (model_size.x / 2),
+                                             start.y + (model_size.y / 2),
+                                             start.z + (model_size.z / 2)));
+        }
+      }
+    }
+    if translations.len() == 0
+    { return Err(~"No voxels to export"); }
+
+    /* Scene graph: root transform -> group -> (transform -> shape) per model. */
+    let mut content = ~[];
+    push_transform(&mut content, 0, 1, -1, None);
+    push_chunk(&mut body, "nTRN", content, []);
+
+    let mut content = ~[];
+    push_i32(&mut content, 1);
+    push_i32(&mut content, 0); /* No attributes. */
+    push_i32(&mut content, translations.len() as i32);
+    for i in range(0, translations.len())
+    { push_i32(&mut content, (2 + (i * 2)) as i32); }
+    push_chunk(&mut body, "nGRP", content, []);
+
+    for (i, translation) in translations.iter().enumerate()
+    {
+      let id = (2 + (i * 2)) as i32;
+
+      let mut content = ~[];
+      push_transform(&mut content, id, id + 1, 0, Some(*translation));
+      push_chunk(&mut body, "nTRN", content, []);
+
+      let mut content = ~[];
+      push_i32(&mut content, id + 1);
+      push_i32(&mut content, 0); /* No attributes. */
+      push_i32(&mut content, 1); /* One model. */
+      push_i32(&mut content, i as i32);
+      push_i32(&mut content, 0); /* No model attributes. */
+      push_chunk(&mut body, "nSHP", content, []);
+    }
+
+    /* Palette entry i holds color index i + 1. */
+    let mut content = ~[];
+    for i in range(0, PALETTE_SIZE + 1)
+    {
+      let color = if i < palette.len() { palette[i] } else { math::Vec3f::zero() };
+      content.push((color.x * 255.0).round() as u8);
+      content.push((color.y * 255.0).round() as u8);
+      content.push((color.z * 255.0).round() as u8);
+      content.push(255);
+    }
+    push_chunk(&mut body, "RGBA", content, []);
+
+    /* MagicaVoxel skips chunks it doesn't know, so the grid's world
+     * transform rides along in our own. */
+    let mut content = ~[];
+    push_f32(&mut content, self.origin.x);
+    push_f32(&mut content, self.origin.y);
+    push_f32(&mut content, self.origin.z);
+    push_f32(&mut content, self.voxel_size);
+    push_i32(&mut content, self.dimensions.x);
+    push_i32(&mut content, self.dimensions.y);
+    push_i32(&mut content, self.dimensions.z);
+    push_chunk(&mut body, "Q3XF", content, []);
+
+    let mut out = ~[];
+    out.push_all("VOX ".as_bytes());
+    push_i32(&mut out, VERSION);
+    push_chunk(&mut out, "MAIN", [], body);
+
+    let fio = File::create(&Path::new(file));
+    if fio.is_none()
+    { return Err(format!("Failed to write file: {}", file)); }
+    fio.unwrap().write(out);
+
+    log_info!("Exported {} voxels in {} models to {}",
+              self.voxels.len(), translations.len(), file);
+    Ok(())
+  }
+
+  /* Builds a map from a .vox file. Without our transform chunk,
+   * the grid sits at the origin with a voxel size of one. */
+  pub fn new_from_vox(file: &str) -> Result<@mut Map, ~str>
+  {
+    let fio = File::open(&Path::new(file));
+    if fio.is_none()
+    { return Err(format!("Failed to read file: {}", file)); }
+    let mut buf = Buffer::new(fio.unwrap().read_to_end());
+
+    if buf.read_id() != Some(~"VOX ")
+    { return Err(format!("Invalid .vox magic in {}", file)); }
+    let version = buf.read_i32();
+    if version.is_none()
+    { return Err(~"Truncated .vox header"); }
+    if buf.read_id() != Some(~"MAIN")
+    { return Err(~"Missing MAIN chunk"); }
+    let main_content = buf.read_i32().unwrap_or(0);
+    let _ = buf.read_i32();
+    if main_content < 0
+    { return Err(~"Invalid MAIN chunk size"); }
+    buf.pos += main_content as uint;
+
+    let mut sizes = ~[];
+    let mut models: ~[~[Vox_Voxel]] = ~[];
+    let mut palette = default_palette();
+    /* (node, child, translation) */
+    let mut transforms: ~[(i32, i32, math::Vec3i)] = ~[];
+    /* (node, model) */
+    let mut shapes: ~[(i32, i32)] = ~[];
+    let mut transform: Option<(math::Vec3f, f32, math::Vec3i)> = None;
+
+    while buf.pos < buf.data.len()
+    {
+      let id = buf.read_id();
+      let content = buf.read_i32();
+      let children = buf.read_i32();
+      if id.is_none() || content.is_none() || children.is_none()
+      { return Err(~"Truncated .vox chunk"); }
+      let id = id.unwrap();
+
+      /* Sizes come straight from the file; negative ones would wrap
+       * around and could send us backward, to loop forever. */
+      let (content, children) = (content.unwrap(), children.unwrap());
+      if content < 0 || children < 0
+      { return Err(format!("Invalid {} chunk size", id)); }
+      let next = buf.pos + (content as uint) + (children as uint);
+      if next > buf.data.len()
+      { return Err(format!("Truncated {} chunk", id)); }
+
+      match id.as_slice()
+      {
+        "SIZE" =>
+        {
+          let x = buf.read_i32().unwrap_or(0);
+          let y = buf.read_i32().unwrap_or(0);
+          let z = buf.read_i32().unwrap_or(0);
+          sizes.push(math::Vec3i::new(x, y, z));
+        }
+        "XYZI" =>
+        {
+          let count = buf.read_i32().unwrap_or(0);
+          if count < 0 || buf.pos + ((count as uint) * 4) > next
+          { return Err(~"Truncated XYZI chunk"); }
+
+          let mut model = vec::with_capacity(count as uint);
+          for _ in range(0, count)
+          {
+            let bytes = buf.data.slice(buf.pos, buf.pos + 4);
+            model.push(Vox_Voxel
+            {
+              position: math::Vec3i::new(bytes[0] as i32, bytes[1] as i32, bytes[2] as i32),
+              color: bytes[3],
+            });
+            buf.pos += 4;
+          }
+          models.push(model);
+        }
+        "RGBA" =>
+        {
+          if buf.pos + (PALETTE_SIZE * 4) > next
+          { return Err(~"Truncated RGBA chunk"); }
+
+          for i in range(0, PALETTE_SIZE)
+          {
+            let bytes = buf.data.slice(buf.pos + (i * 4), buf.pos + (i * 4) + 3);
+            palette[i + 1] = math::Vec3f::new(bytes[0] as f32 / 255.0,
+                                              bytes[1] as f32 / 255.0,
+                                              bytes[2] as f32 / 255.0);
+          }
+        }
+        "nTRN" =>
+        {
+          let node = buf.read_i32().unwrap_or(-1);
+          let _ = read_dict(&mut buf);
+          let child = buf.read_i32().unwrap_or(-1);
+          let _reserved = buf.read_i32();
+          let _layer = buf.read_i32();
+          let frames = buf.read_i32().unwrap_or(0);
+
+          let mut translation = math::Vec3i::zero();
+          if frames > 0
+          {
+            for &(ref key, ref val) in read_dict(&mut buf).iter()
+            {
+              if key.as_slice() != "_t"
+              { continue; }
+
+              let parts: ~[i32] = val.split_iter(' ').filter_map(|p| from_str::<i32>(p)).collect();
+              if parts.len() == 3
+              { translation = math::Vec3i::new(parts[0], parts[1], parts[2]); }
+            }
+          }
+          transforms.push((node, child, translation));
+        }
+        "nSHP" =>
+        {
+          let node = buf.read_i32().unwrap_or(-1);
+          let _ = read_dict(&mut buf);
+          let count = buf.read_i32().unwrap_or(0);
+          if count > 0
+          { shapes.push((node, buf.read_i32().unwrap_or(-1))); }
+        }
+        "Q3XF" =>
+        {
+          let ox = buf.read_f32().unwrap_or(0.0);
+          let oy = buf.read_f32().unwrap_or(0.0);
+          let oz = buf.read_f32().unwrap_or(0.0);
+          let size = buf.read_f32().unwrap_or(1.0);
+          let dx = buf.read_i32().unwrap_or(0);
+          let dy = buf.read_i32().unwrap_or(0);
+          let dz = buf.read_i32().unwrap_or(0);
+          transform = Some((math::Vec3f::new(ox, oy, oz), size, math::Vec3i::new(dx, dy, dz)));
+        }
+        _ => { } /* Materials, layers, cameras, etc. are of no use to us. */
+      }
+
+      buf.pos = next;
+    }
+
+    if models.len() == 0 || models.len() != sizes.len()
+    { return Err(format!("Invalid model count in {}", file)); }
+
+    /* Move each model into world space, based on its shape's transform. */
+    let mut world = ~[];
+    for (i, model) in models.iter().enumerate()
+    {
+      let mut translation = math::Vec3i::zero();
+      let mut centered = false;
+      for &(shape_node, shape_model) in shapes.iter()
+      {
+        if shape_model != i as i32
+        { continue; }
+        for &(_, child, t) in transforms.iter()
+        {
+          if child == shape_node
+          { translation = t; centered = true; }
+        }
+      }
+
+      /* Without a scene graph, models sit at their own corner. */
+      let offset = if centered
+      {
+        math::Vec3i::new(translation.x - (sizes[i].x / 2),
+                         translation.y - (sizes[i].y / 2),
+                         translation.z - (sizes[i].z / 2))
+      }
+      else
+      { math::Vec3i::zero() };
+
+      for vox in model.iter()
+      { world.push(Vox_Voxel { position: vox.position + offset, color: vox.color }); }
+    }
+
+    /* Swap into our axes, flipping Z against the original depth of the grid. */
+    let mut max_y = world[0].position.y;
+    for vox in world.iter()
+    { max_y = cmp::max(max_y, vox.position.y); }
+    let (origin, voxel_size, depth) = match transform
+    {
+      Some((origin, size, dims)) => (origin, size, dims.z),
+      None => (math::Vec3f::zero(), 1.0f32, max_y + 1),
+    };
+
+    let mut cells = vec::with_capacity(world.len());
+    let mut min = math::Vec3i::new(0, 0, 0);
+    let mut max = match transform
+    {
+      Some((_, _, dims)) => math::Vec3i::new(dims.x - 1, dims.y - 1, dims.z - 1),
+      None => world[0].position,
+    };
+    if transform.is_none()
+    { min = math::Vec3i::new(world[0].position.x, world[0].position.z, depth - 1 - world[0].position.y); }
+    for vox in world.iter()
+    {
+      let cell = math::Vec3i::new(vox.position.x, vox.position.z, depth - 1 - vox.position.y);
+      min = math::Vec3i::new(cmp::min(min.x, cell.x), cmp::min(min.y, cell.y), cmp::min(min.z, cell.z));
+      max = math::Vec3i::new(cmp::max(max.x, cell.x), cmp::max(max.y, cell.y), cmp::max(max.z, cell.z));
+      cells.push((cell, vox.color));
+    }
+
+    /* Voxels added beyond the original grid grow it, without moving the rest. */
+    let dimensions = (max - min) + math::Vec3i::new(1, 1, 1);
+    let origin = origin + min.to_f() * voxel_size;
+    let voxels = do cells.map |&(cell, color)|
+    {
+      let pos = cell - min;
+      Vertex::new(pos.to_f(), palette[color])
+    };
+
+    log_info!("Imported {} voxels from {} models in {}", voxels.len(), models.len(), file);
+    Map::new_with_voxels(dimensions, voxel_size, origin, voxels)
+  }
+}
+
+/* MagicaVoxel's default palette, used when a file has no RGBA chunk.
+ * Index zero is empty space. */
+fn default_palette() -> ~[math::Vec3f]
+{
+  let mut palette = ~[ math::Vec3f::zero() ];
+
+  /* The web-safe color cube, brightest first, without black. */
+  for r in range(0, 6).invert()
+  {
+    for g in range(0, 6).invert()
+    {
+      for b in range(0, 6).invert()
+      {
+        if r == 0 && g == 0 && b == 0
+        { continue; }
+        palette.push(math::Vec3f::new(r as f32 * 0.2, g as f32 * 0.2, b as f32 * 0.2));
+      }
+    }
+  }
+
+  /* Red, green, blue, and gray ramps. */
+  let ramp = [ 0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11 ];
+  for channel in range(0, 4)
+  {
+    for &val in ramp.iter()
+    {
+      let v = val as f32 / 255.0;
+      palette.push(match channel
+      {
+        0 => math::Vec3f::new(v, 0.0, 0.0),
+        1 => math::Vec3f::new(0.0, v, 0.0),
+        2 => math::Vec3f::new(0.0, 0.0, v),
+        _ => math::Vec3f::new(v, v, v),
+      });
+    }
+  }
+
+  palette
+}
+
+fn push_chunk(buf: &mut ~[u8], id: &str, content: &[u8], children: &[u8])
+{
+  buf.push_all(id.as_bytes());
+  push_i32(buf, content.len() as i32);
+  push_i32(buf, children.len() as i32);
+  buf.push_all(content);
+  buf.push_all(children);
+}
+
+/* A transform node with a single frame. */
+fn push_transform(buf: &mut ~[u8], node: i32, child: i32, layer: i32,
+                  translation: Option<math::Vec3i>)
+{
+  push_i32(buf, node);
+  push_i32(buf, 0); /* No attributes. */
+  push_i32(buf, child);
+  push_i32(buf, -1); /* Reserved. */
+  push_i32(buf, layer);
+  push_i32(buf, 1); /* One frame. */
+  match translation
+  {
+    Some(t) =>
+    {
+      push_i32(buf, 1);
+      push_string(buf, "_t");
+      push_string(buf, format!("{} {} {}", t.x, t.y, t.z));
+    }
+    None => { push_i32(buf, 0); }
+  }
+}
+
+/* A count, followed by that many key and value strings. */
+fn read_dict(buf: &mut Buffer) -> ~[(~str, ~str)]
+{
+  let mut dict = ~[];
+  let count = buf.read_i32().unwrap_or(0);
+  for _ in range(0, count)
+  {
+    let key = buf.read_string();
+    let val = buf.read_string();
+    if key.is_none() || val.is_none()
+    { break; }
+    dict.push((key.unwrap(), val.unwrap()));
+  }
+
+  dict
+}
+
+#[cfg(test)]
+mod test
+{
+  use std::os;
+  use math;
+  use super::super::{ Map, Vertex, Visible };
+
+  #[test]
+  fn round_trip()
+  {
+    /* Wider than a model, so that two are written along X. */
+    let cells = [ ((0, 0, 0), (1.0, 0.0, 0.0)),
+                  ((255, 0, 1), (0.2, 0.4, 0.6)),
+                  ((256, 1, 0), (0.0, 0.0, 1.0)),
+                  ((299, 2, 1), (0.0, 1.0, 0.0)) ];
+    let voxels = do cells.map |&((x, y, z), (r, g, b))|
+    { Vertex::new(math::Vec3f::new(x as f32, y as f32, z as f32), math::Vec3f::new(r, g, b)) };
+    let map = Map::new_with_voxels(math::Vec3i::new(300, 3, 2), 0.5,
+                                   math::Vec3f::new(-3.0, 1.25, 7.0), voxels).unwrap();
+
+    let path = os::tmpdir().join("vox_round_trip.vox");
+    let file = path.as_str().unwrap();
+    assert!(map.export_vox(file).is_ok());
+    let loaded = Map::new_from_vox(file);
+    os::remove_file(&path);
+    let loaded = loaded.unwrap();
+
+    assert!(loaded.dimensions == map.dimensions);
+    assert!(loaded.origin == map.origin);
+    assert!(loaded.voxel_size.approx_eq(&map.voxel_size));
+    assert!(loaded.voxels.len() == cells.len());
+
+    let states = loaded.states.get_ref();
+    for &((x, y, z), (r, g, b)) in cells.iter()
+    {
+      let state = states[loaded.cell_index(&math::Vec3i::new(x, y, z))];
+      assert!((state & Visible) != 0);
+
+      /* Colors go through a byte each. */
+      let color = loaded.voxels[state & !Visible].color;
+      assert!((color.x - r).abs() < 0.003);
+      assert!((color.y - g).abs() < 0.003);
+      assert!((color.z - b).abs() < 0.003);
+    }
+  }
+}